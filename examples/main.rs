@@ -2,7 +2,7 @@ use bevy::app::{App, Startup, Update};
 use bevy::asset::{AssetServer, Assets};
 use bevy::color::palettes::css::*;
 use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
-use bevy::math::Quat;
+use bevy::math::{Quat, Vec2};
 use bevy::prelude::{
     Camera, Camera2dBundle, Circle, ClearColor, Color, ColorMaterial, Commands, Component, Mesh,
     Query, Res, ResMut, Transform, Window, With, Without,
@@ -12,8 +12,10 @@ use bevy::time::Time;
 use bevy::utils::default;
 use bevy::DefaultPlugins;
 use bevy_swash::{
-    JustifyOutlinedText, OutlineStyle, OutlinedFont, OutlinedFontStyle, OutlinedText,
-    OutlinedText2dBundle, OutlinedTextPlugin, OutlinedTextSection,
+    tag_from_bytes, BackgroundStyle, JustifyOutlinedText, LineHeight, OutlineLayer,
+    OutlinePlacement, OutlineStyle, OutlinedFont, OutlinedFontStyle, OutlinedText,
+    OutlinedText2dBundle, OutlinedTextInput, OutlinedTextPlugin, OutlinedTextRenderMode,
+    OutlinedTextSection, TextDecoration, TextFill, TextFillMapping,
 };
 use std::f32::consts::PI;
 
@@ -36,26 +38,41 @@ fn setup(
                 sections: vec![
                     OutlinedTextSection {
                         value: "Outline".to_string(),
-                        color: ORANGE.into(),
-                        outline: OutlineStyle::Outline {
-                            width: 10.0,
-                            color: RED.into(),
+                        color: TextFill::LinearGradient {
+                            from: YELLOW.into(),
+                            to: ORANGE.into(),
+                            angle: -PI / 2.0,
+                            mapping: TextFillMapping::Block,
                         },
+                        outline: OutlineStyle::outline(10.0, RED),
+                        ..default()
                     },
                     OutlinedTextSection {
                         value: "!".to_string(),
                         color: AQUA.into(),
-                        outline: OutlineStyle::Outline {
-                            width: 10.0,
-                            color: BLUE.into(),
-                        },
+                        outline: OutlineStyle::Stacked(vec![
+                            OutlineLayer {
+                                width: 6.0,
+                                color: Color::BLACK.into(),
+                                ..default()
+                            },
+                            OutlineLayer {
+                                width: 16.0,
+                                color: BLUE.into(),
+                                ..default()
+                            },
+                        ]),
+                        ..default()
                     },
                 ],
                 justify: JustifyOutlinedText::Left,
                 font_style: OutlinedFontStyle {
                     font: asset_server.load::<OutlinedFont>("fonts/Montserrat-Bold.ttf"),
                     size: 160.0,
+                    ..default()
                 },
+                render_mode: OutlinedTextRenderMode::Sdf { softness: 0.0 },
+                ..default()
             },
             text_anchor: Anchor::Center,
             transform: Transform::from_xyz(0.0, 0.0, 5.0),
@@ -63,23 +80,40 @@ fn setup(
         })
         .insert(Spinner);
 
-    commands.spawn(OutlinedText2dBundle {
-        text: OutlinedText {
-            sections: vec![OutlinedTextSection {
-                value: "Bevy, bevy, bevy...\nAnother line".to_string(),
-                color: Color::WHITE,
-                outline: OutlineStyle::None,
-            }],
-            justify: JustifyOutlinedText::Center,
-            font_style: OutlinedFontStyle {
-                font: asset_server.load::<OutlinedFont>("fonts/Montserrat-Regular.ttf"),
-                size: 20.0,
+    commands.spawn((
+        OutlinedText2dBundle {
+            text: OutlinedText {
+                sections: vec![OutlinedTextSection {
+                    value: "Bevy, bevy, bevy...\nAnother line".to_string(),
+                    color: Color::WHITE.into(),
+                    outline: OutlineStyle::None,
+                    underline: Some(TextDecoration::default()),
+                    ..default()
+                }],
+                justify: JustifyOutlinedText::Center,
+                font_style: OutlinedFontStyle {
+                    font: asset_server.load::<OutlinedFont>("fonts/Montserrat-Regular.ttf"),
+                    size: 20.0,
+                    line_height: LineHeight::Multiplier(1.4),
+                    ..default()
+                },
+                background: Some(BackgroundStyle {
+                    color: Color::srgba(0.0, 0.0, 0.0, 0.5),
+                    padding: Vec2::new(8.0, 4.0),
+                    corner_radius: 6.0,
+                }),
+                ..default()
             },
+            text_anchor: Anchor::BottomLeft,
+            transform: Transform::from_xyz(-100.0, -100.0, 7.0),
+            ..default()
         },
-        text_anchor: Anchor::BottomLeft,
-        transform: Transform::from_xyz(-100.0, -100.0, 7.0),
-        ..default()
-    });
+        OutlinedTextInput {
+            multiline: true,
+            focused: true,
+            ..default()
+        },
+    ));
 
     commands.spawn((
         OutlinedText2dBundle {
@@ -87,26 +121,33 @@ fn setup(
                 sections: vec![
                     OutlinedTextSection {
                         value: "FPS: ".to_string(),
-                        color: Color::BLACK,
-                        outline: OutlineStyle::Outline {
-                            width: 5.0,
-                            color: Color::WHITE,
-                        },
+                        color: Color::BLACK.into(),
+                        outline: OutlineLayer {
+                            placement: OutlinePlacement::Outside,
+                            ..OutlineLayer::new(2.5, Color::WHITE)
+                        }
+                        .into(),
+                        ..default()
                     },
                     OutlinedTextSection {
                         value: "".to_string(),
-                        color: Color::BLACK,
-                        outline: OutlineStyle::Outline {
-                            width: 5.0,
-                            color: RED.into(),
-                        },
+                        color: Color::BLACK.into(),
+                        outline: OutlineLayer {
+                            placement: OutlinePlacement::Outside,
+                            ..OutlineLayer::new(2.5, RED)
+                        }
+                        .into(),
+                        ..default()
                     },
                 ],
                 justify: JustifyOutlinedText::Left,
                 font_style: OutlinedFontStyle {
                     font: asset_server.load::<OutlinedFont>("fonts/Montserrat-Italic.ttf"),
                     size: 40.0,
+                    features: vec![(tag_from_bytes(b"tnum"), 1)],
+                    ..default()
                 },
+                ..default()
             },
             text_anchor: Anchor::TopLeft,
             transform: Transform::from_xyz(-300.0, 300.0, 5.0),
@@ -130,6 +171,7 @@ fn setup(
     });
 }
 
+#[allow(clippy::type_complexity)]
 fn update_fps_text(
     window_query: Query<&Window>,
     camera_query: Query<&Transform, With<Camera>>,
@@ -162,7 +204,7 @@ fn main() {
     let mut app = App::new();
 
     app.add_plugins(DefaultPlugins)
-        .add_plugins(FrameTimeDiagnosticsPlugin::default())
+        .add_plugins(FrameTimeDiagnosticsPlugin)
         .add_plugins(OutlinedTextPlugin)
         .insert_resource(ClearColor(Color::BLACK))
         .add_systems(Startup, setup)
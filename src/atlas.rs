@@ -0,0 +1,550 @@
+use bevy::math::{URect, UVec2};
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::sprite::{DynamicTextureAtlasBuilder, TextureAtlasLayout};
+use bevy::utils::HashMap;
+use std::sync::OnceLock;
+use swash::scale::image::Content;
+use swash::{CacheKey, GlyphId};
+
+use crate::sdf::SDF_SIZE;
+use crate::SwashImage;
+
+const ATLAS_PAGE_SIZE: u32 = 1024;
+const ATLAS_PADDING: u32 = 1;
+
+pub(crate) const SUBPIXEL_STEPS: u8 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GlyphAtlasKind {
+    Coverage,
+    DistanceField,
+    Shadow,
+    Color,
+    Silhouette,
+    Decoration,
+    Background,
+}
+
+/// Font key for entries that are drawn without a font.
+fn no_font() -> CacheKey {
+    static NO_FONT: OnceLock<CacheKey> = OnceLock::new();
+    *NO_FONT.get_or_init(CacheKey::new)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GlyphAtlasKey {
+    pub kind: GlyphAtlasKind,
+    pub font: CacheKey,
+    pub variations: u64,
+    pub size: u32,
+    pub glyph_id: GlyphId,
+    pub outline_width: u32,
+    pub stroke_style: u64,
+    pub blur_radius: u32,
+    pub corner_radius: u32,
+    pub run: u64,
+    pub subpixel_offset: u8,
+}
+
+impl GlyphAtlasKey {
+    pub fn new(
+        font: CacheKey,
+        variations: u64,
+        size: f32,
+        glyph_id: GlyphId,
+        outline_width: f32,
+        subpixel_offset: u8,
+    ) -> Self {
+        Self {
+            kind: GlyphAtlasKind::Coverage,
+            font,
+            variations,
+            size: size.to_bits(),
+            glyph_id,
+            outline_width: outline_width.to_bits(),
+            stroke_style: 0,
+            blur_radius: 0.0f32.to_bits(),
+            corner_radius: 0.0f32.to_bits(),
+            run: 0,
+            subpixel_offset,
+        }
+    }
+
+    pub fn distance_field(font: CacheKey, variations: u64, glyph_id: GlyphId) -> Self {
+        Self {
+            kind: GlyphAtlasKind::DistanceField,
+            ..Self::new(font, variations, SDF_SIZE, glyph_id, 0.0, 0)
+        }
+    }
+
+    pub fn decoration(font: CacheKey, variations: u64, thickness: f32, outline_width: f32) -> Self {
+        Self {
+            kind: GlyphAtlasKind::Decoration,
+            ..Self::new(font, variations, thickness, 0, outline_width, 0)
+        }
+    }
+
+    /// Key for the blurred shadow of a run of glyphs, identified by a hash of
+    /// the glyphs, their relative positions and the outline they are drawn with.
+    pub fn shadow(font: CacheKey, run: u64, blur_radius: f32) -> Self {
+        Self {
+            kind: GlyphAtlasKind::Shadow,
+            blur_radius: blur_radius.to_bits(),
+            run,
+            ..Self::new(font, 0, 0.0, 0, 0.0, 0)
+        }
+    }
+
+    /// Key for a rounded background tile. The tile only depends on its corner
+    /// radius in physical pixels, so it is shared across fonts and scale
+    /// factors.
+    pub fn background(corner_radius: f32) -> Self {
+        Self {
+            kind: GlyphAtlasKind::Background,
+            corner_radius: corner_radius.to_bits(),
+            ..Self::new(no_font(), 0, 0.0, 0, 0.0, 0)
+        }
+    }
+
+    pub fn stroke_style(self, stroke_style: u64) -> Self {
+        Self {
+            stroke_style,
+            ..self
+        }
+    }
+
+    pub fn color(self) -> Self {
+        Self {
+            kind: GlyphAtlasKind::Color,
+            ..self
+        }
+    }
+
+    pub fn silhouette(self) -> Self {
+        Self {
+            kind: GlyphAtlasKind::Silhouette,
+            ..self
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GlyphAtlasEntry {
+    pub image: Handle<Image>,
+    pub rect: URect,
+    pub left: i32,
+    pub top: i32,
+    pub color: bool,
+}
+
+struct GlyphAtlasSlot {
+    entry: GlyphAtlasEntry,
+    references: usize,
+}
+
+struct GlyphAtlasPage {
+    image: Handle<Image>,
+    layout: TextureAtlasLayout,
+    builder: DynamicTextureAtlasBuilder,
+    live_glyphs: usize,
+    pending_writes: Vec<(URect, Vec<u8>)>,
+}
+
+impl GlyphAtlasPage {
+    fn new(images: &mut Assets<Image>, size: u32) -> Self {
+        let image = Image::new_fill(
+            Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[255, 255, 255, 0],
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        );
+
+        Self {
+            image: images.add(image),
+            layout: TextureAtlasLayout::new_empty(UVec2::splat(size)),
+            builder: DynamicTextureAtlasBuilder::new(UVec2::splat(size), ATLAS_PADDING),
+            live_glyphs: 0,
+            pending_writes: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, images: &mut Assets<Image>, glyph: &Image) -> Option<URect> {
+        self.builder
+            .add_texture(&mut self.layout, images, glyph, &self.image)
+            .map(|index| self.layout.textures[index])
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GlyphAtlasStatistics {
+    pub pages: usize,
+    pub live_glyphs: usize,
+    pub stale_glyphs: usize,
+}
+
+#[derive(Resource, Default)]
+pub struct GlyphAtlas {
+    pages: Vec<GlyphAtlasPage>,
+    glyphs: HashMap<GlyphAtlasKey, GlyphAtlasSlot>,
+    stale: HashMap<UVec2, Vec<GlyphAtlasKey>>,
+}
+
+impl GlyphAtlas {
+    pub fn get(&self, key: &GlyphAtlasKey) -> Option<&GlyphAtlasEntry> {
+        self.glyphs.get(key).map(|slot| &slot.entry)
+    }
+
+    pub fn statistics(&self) -> GlyphAtlasStatistics {
+        let stale_glyphs = self.stale.values().map(Vec::len).sum();
+
+        GlyphAtlasStatistics {
+            pages: self.pages.len(),
+            live_glyphs: self.pages.iter().map(|page| page.live_glyphs).sum(),
+            stale_glyphs,
+        }
+    }
+
+    pub(crate) fn acquire(
+        &mut self,
+        key: GlyphAtlasKey,
+        images: &mut Assets<Image>,
+        rasterize: impl FnOnce() -> SwashImage,
+    ) -> Option<GlyphAtlasEntry> {
+        if let Some(slot) = self.glyphs.get_mut(&key) {
+            let entry = slot.entry.clone();
+            slot.references += 1;
+
+            if slot.references == 1 {
+                if let Some(stale) = self.stale.get_mut(&entry.rect.size()) {
+                    stale.retain(|stale_key| *stale_key != key);
+                }
+                self.page_mut(&entry.image).live_glyphs += 1;
+            }
+
+            return Some(entry);
+        }
+
+        // Empty glyphs are rasterized again on every use rather than cached,
+        // since nothing releases them and their keys would pile up.
+        let bitmap = rasterize();
+        let entry = self.insert(images, &bitmap)?;
+        self.page_mut(&entry.image).live_glyphs += 1;
+
+        self.glyphs.insert(
+            key,
+            GlyphAtlasSlot {
+                entry: entry.clone(),
+                references: 1,
+            },
+        );
+
+        Some(entry)
+    }
+
+    pub(crate) fn release(&mut self, key: &GlyphAtlasKey) {
+        let Some(slot) = self.glyphs.get_mut(key) else {
+            return;
+        };
+        let entry = slot.entry.clone();
+        if slot.references == 0 {
+            return;
+        }
+
+        slot.references -= 1;
+
+        if slot.references == 0 {
+            self.stale.entry(entry.rect.size()).or_default().push(*key);
+            self.page_mut(&entry.image).live_glyphs -= 1;
+        }
+    }
+
+    /// Copies the glyphs that replaced stale entries into their pages, touching
+    /// each modified page once instead of once per glyph.
+    pub(crate) fn write_pending(&mut self, images: &mut Assets<Image>) {
+        for page in &mut self.pages {
+            if page.pending_writes.is_empty() {
+                continue;
+            }
+
+            let writes = std::mem::take(&mut page.pending_writes);
+            let Some(image) = images.get_mut(&page.image) else {
+                continue;
+            };
+            let page_width = image.width() as usize;
+
+            for (rect, data) in writes {
+                let row_len = rect.width() as usize * 4;
+                for (row, source) in data.chunks_exact(row_len).enumerate() {
+                    let start =
+                        ((rect.min.y as usize + row) * page_width + rect.min.x as usize) * 4;
+                    image.data[start..start + row_len].copy_from_slice(source);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn free_unused_pages(&mut self, images: &mut Assets<Image>) {
+        let page_count = self.pages.len();
+        let last_page = page_count.saturating_sub(1);
+        let mut index = 0;
+
+        self.pages.retain(|page| {
+            let keep = index == last_page || page.live_glyphs > 0;
+            index += 1;
+
+            if !keep {
+                images.remove(&page.image);
+            }
+            keep
+        });
+
+        if self.pages.len() == page_count {
+            return;
+        }
+
+        let pages = &self.pages;
+        self.glyphs
+            .retain(|_, slot| pages.iter().any(|page| page.image == slot.entry.image));
+
+        let glyphs = &self.glyphs;
+        for stale in self.stale.values_mut() {
+            stale.retain(|key| glyphs.contains_key(key));
+        }
+    }
+
+    fn page_mut(&mut self, image: &Handle<Image>) -> &mut GlyphAtlasPage {
+        self.pages
+            .iter_mut()
+            .find(|page| page.image == *image)
+            .expect("glyph atlas entry points to a freed page")
+    }
+
+    fn insert(
+        &mut self,
+        images: &mut Assets<Image>,
+        bitmap: &SwashImage,
+    ) -> Option<GlyphAtlasEntry> {
+        if bitmap.placement.width == 0 || bitmap.placement.height == 0 {
+            return None;
+        }
+
+        let color = bitmap.content == Content::Color;
+        let glyph = if color {
+            color_to_image(bitmap)
+        } else {
+            coverage_to_image(bitmap)
+        };
+
+        if let Some(entry) = self.reuse_stale(&glyph) {
+            return Some(GlyphAtlasEntry {
+                left: bitmap.placement.left,
+                top: bitmap.placement.top,
+                color,
+                ..entry
+            });
+        }
+
+        let rect = self.pages.last_mut().and_then(|page| {
+            page.add(images, &glyph)
+                .map(|rect| (page.image.clone(), rect))
+        });
+
+        let (image, rect) = match rect {
+            Some(placed) => placed,
+            None => {
+                let size = ATLAS_PAGE_SIZE
+                    .max((bitmap.placement.width + ATLAS_PADDING).next_power_of_two())
+                    .max((bitmap.placement.height + ATLAS_PADDING).next_power_of_two());
+
+                let mut page = GlyphAtlasPage::new(images, size);
+                let rect = page.add(images, &glyph)?;
+                let image = page.image.clone();
+                self.pages.push(page);
+
+                (image, rect)
+            }
+        };
+
+        Some(GlyphAtlasEntry {
+            image,
+            rect,
+            left: bitmap.placement.left,
+            top: bitmap.placement.top,
+            color,
+        })
+    }
+
+    fn reuse_stale(&mut self, glyph: &Image) -> Option<GlyphAtlasEntry> {
+        let stale_key = self.stale.get_mut(&glyph.size())?.pop()?;
+        let entry = self.glyphs.remove(&stale_key)?.entry;
+
+        self.page_mut(&entry.image)
+            .pending_writes
+            .push((entry.rect, glyph.data.clone()));
+
+        Some(entry)
+    }
+}
+
+fn coverage_to_image(bitmap: &SwashImage) -> Image {
+    Image::new(
+        Extent3d {
+            width: bitmap.placement.width,
+            height: bitmap.placement.height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        bitmap
+            .data
+            .iter()
+            .flat_map(|alpha| [255, 255, 255, *alpha])
+            .collect::<Vec<u8>>(),
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    )
+}
+
+fn color_to_image(bitmap: &SwashImage) -> Image {
+    Image::new(
+        Extent3d {
+            width: bitmap.placement.width,
+            height: bitmap.placement.height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        bitmap.data.clone(),
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::OnceLock;
+
+    fn key(glyph_id: GlyphId) -> GlyphAtlasKey {
+        static FONT: OnceLock<CacheKey> = OnceLock::new();
+        GlyphAtlasKey::new(*FONT.get_or_init(CacheKey::new), 0, 16.0, glyph_id, 0.0, 0)
+    }
+
+    fn mask(width: u32, height: u32, alpha: u8) -> SwashImage {
+        let mut image = SwashImage::new();
+        image.placement.width = width;
+        image.placement.height = height;
+        image.data = vec![alpha; (width * height) as usize];
+        image
+    }
+
+    fn pixel(images: &Assets<Image>, entry: &GlyphAtlasEntry) -> [u8; 4] {
+        let image = images.get(&entry.image).unwrap();
+        let start = (entry.rect.min.y * image.width() + entry.rect.min.x) as usize * 4;
+        image.data[start..start + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn acquire_counts_references() {
+        let mut atlas = GlyphAtlas::default();
+        let mut images = Assets::<Image>::default();
+        let mut rasterized = 0;
+
+        for _ in 0..2 {
+            atlas.acquire(key(1), &mut images, || {
+                rasterized += 1;
+                mask(4, 4, 255)
+            });
+        }
+        assert_eq!(rasterized, 1);
+
+        atlas.release(&key(1));
+        assert_eq!(atlas.statistics().live_glyphs, 1);
+        assert_eq!(atlas.statistics().stale_glyphs, 0);
+
+        atlas.release(&key(1));
+        atlas.release(&key(1));
+        let statistics = atlas.statistics();
+        assert_eq!(statistics.live_glyphs, 0);
+        assert_eq!(statistics.stale_glyphs, 1);
+
+        atlas.acquire(key(1), &mut images, || unreachable!());
+        assert_eq!(atlas.statistics().live_glyphs, 1);
+        assert_eq!(atlas.statistics().stale_glyphs, 0);
+    }
+
+    #[test]
+    fn empty_glyphs_are_not_stored() {
+        let mut atlas = GlyphAtlas::default();
+        let mut images = Assets::<Image>::default();
+
+        assert!(atlas
+            .acquire(key(1), &mut images, || mask(0, 0, 0))
+            .is_none());
+        assert!(atlas
+            .acquire(key(2), &mut images, || mask(0, 0, 0))
+            .is_none());
+        assert!(atlas.glyphs.is_empty());
+        assert_eq!(atlas.statistics().pages, 0);
+    }
+
+    #[test]
+    fn stale_entries_are_reused_by_same_size_glyphs() {
+        let mut atlas = GlyphAtlas::default();
+        let mut images = Assets::<Image>::default();
+
+        let stale = atlas
+            .acquire(key(1), &mut images, || mask(4, 4, 10))
+            .unwrap();
+        atlas.release(&key(1));
+
+        let other_size = atlas
+            .acquire(key(2), &mut images, || mask(5, 4, 20))
+            .unwrap();
+        assert_ne!(other_size.rect, stale.rect);
+
+        let reused = atlas
+            .acquire(key(3), &mut images, || mask(4, 4, 30))
+            .unwrap();
+        assert_eq!(reused.rect, stale.rect);
+        assert!(atlas.get(&key(1)).is_none());
+        assert_eq!(atlas.statistics().stale_glyphs, 0);
+
+        assert_eq!(pixel(&images, &reused), [255, 255, 255, 10]);
+        atlas.write_pending(&mut images);
+        assert_eq!(pixel(&images, &reused), [255, 255, 255, 30]);
+        assert_eq!(pixel(&images, &other_size), [255, 255, 255, 20]);
+    }
+
+    #[test]
+    fn unused_pages_are_freed() {
+        let mut atlas = GlyphAtlas::default();
+        let mut images = Assets::<Image>::default();
+
+        let first = atlas
+            .acquire(key(1), &mut images, || mask(4, 4, 255))
+            .unwrap();
+        let large = atlas
+            .acquire(key(2), &mut images, || mask(1100, 1100, 255))
+            .unwrap();
+        assert_ne!(first.image, large.image);
+        assert_eq!(atlas.statistics().pages, 2);
+
+        atlas.release(&key(1));
+        atlas.free_unused_pages(&mut images);
+        assert_eq!(atlas.statistics().pages, 1);
+        assert!(atlas.get(&key(1)).is_none());
+        assert!(images.get(&first.image).is_none());
+
+        // The last page is kept even when empty so new glyphs have somewhere to go.
+        atlas.release(&key(2));
+        atlas.free_unused_pages(&mut images);
+        assert_eq!(atlas.statistics().pages, 1);
+        assert!(images.get(&large.image).is_some());
+    }
+}
@@ -0,0 +1,123 @@
+use unicode_bidi::{BidiInfo, Level};
+
+use crate::OutlinedTextDirection;
+
+pub(crate) fn is_rtl(level: u8) -> bool {
+    level % 2 == 1
+}
+
+/// Resolves the embedding levels of every char in `text`, along with the level
+/// of the paragraph it belongs to. Line-based reordering (L1) is left to the
+/// caller since lines are only known after shaping.
+pub(crate) fn resolve_levels(text: &str, direction: OutlinedTextDirection) -> (Vec<u8>, Vec<u8>) {
+    let default_level = match direction {
+        OutlinedTextDirection::Auto => None,
+        OutlinedTextDirection::LeftToRight => Some(Level::ltr()),
+        OutlinedTextDirection::RightToLeft => Some(Level::rtl()),
+    };
+    let info = BidiInfo::new(text, default_level);
+
+    let mut levels = Vec::with_capacity(text.len());
+    let mut paragraph_levels = Vec::with_capacity(text.len());
+    let mut paragraphs = info.paragraphs.iter().peekable();
+
+    for (offset, _) in text.char_indices() {
+        while paragraphs
+            .next_if(|paragraph| paragraph.range.end <= offset)
+            .is_some()
+        {}
+
+        levels.push(info.levels[offset].number());
+        paragraph_levels.push(
+            paragraphs
+                .peek()
+                .map_or(0, |paragraph| paragraph.level.number()),
+        );
+    }
+
+    (levels, paragraph_levels)
+}
+
+pub(crate) fn visual_order(levels: &[u8]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..levels.len()).collect();
+
+    let Some(&highest) = levels.iter().max() else {
+        return order;
+    };
+    let Some(lowest_odd) = levels.iter().map(|level| level | 1).min() else {
+        return order;
+    };
+
+    for level in (lowest_odd..=highest).rev() {
+        let mut position = 0;
+
+        while position < order.len() {
+            if levels[order[position]] < level {
+                position += 1;
+                continue;
+            }
+
+            let start = position;
+            while position < order.len() && levels[order[position]] >= level {
+                position += 1;
+            }
+
+            order[start..position].reverse();
+        }
+    }
+
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_direction_follows_first_strong_char() {
+        let (levels, paragraph_levels) = resolve_levels("abc אבג", OutlinedTextDirection::Auto);
+        assert_eq!(levels, [0, 0, 0, 0, 1, 1, 1]);
+        assert_eq!(paragraph_levels, [0; 7]);
+
+        let (levels, paragraph_levels) = resolve_levels("אבג abc", OutlinedTextDirection::Auto);
+        assert_eq!(levels, [1, 1, 1, 1, 2, 2, 2]);
+        assert_eq!(paragraph_levels, [1; 7]);
+    }
+
+    #[test]
+    fn numbers_in_rtl_paragraph() {
+        let (levels, _) = resolve_levels("אבג 123", OutlinedTextDirection::Auto);
+        assert_eq!(levels, [1, 1, 1, 1, 2, 2, 2]);
+
+        let (levels, _) = resolve_levels("123", OutlinedTextDirection::RightToLeft);
+        assert_eq!(levels, [2, 2, 2]);
+    }
+
+    #[test]
+    fn paragraphs_resolve_independently() {
+        let (levels, paragraph_levels) = resolve_levels("ab\nאב", OutlinedTextDirection::Auto);
+        assert_eq!(levels, [0, 0, 0, 1, 1]);
+        assert_eq!(paragraph_levels, [0, 0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn isolates_resolve_as_separate_sequences() {
+        // a RLI b PDI c: the isolate is a neutral in the outer sequence and
+        // `b` is an L char at an odd level inside it.
+        let (levels, _) = resolve_levels("a\u{2067}b\u{2069}c", OutlinedTextDirection::LeftToRight);
+        assert_eq!(levels, [0, 0, 2, 0, 0]);
+
+        // The neutral between the two R runs spans the isolate, so it resolves
+        // to R as a single sequence (N1) rather than per level run.
+        let (levels, _) =
+            resolve_levels("א \u{2066}b\u{2069} ב", OutlinedTextDirection::LeftToRight);
+        assert_eq!(levels, [1, 1, 1, 2, 1, 1, 1]);
+    }
+
+    #[test]
+    fn visual_order_reverses_odd_runs() {
+        assert_eq!(visual_order(&[0, 0, 1, 1, 1, 0]), [0, 1, 4, 3, 2, 5]);
+        assert_eq!(visual_order(&[1, 1, 2, 2, 1]), [4, 2, 3, 1, 0]);
+        assert_eq!(visual_order(&[]), Vec::<usize>::new());
+    }
+}
@@ -0,0 +1,299 @@
+use swash::scale::image::Content;
+use swash::zeno::Placement;
+
+use crate::sdf::map_signed_distances;
+use crate::SwashImage;
+
+pub(crate) fn union(a: &SwashImage, b: &SwashImage) -> SwashImage {
+    union_all(&[a, b])
+}
+
+/// Combines any number of coverage bitmaps into one, taking the maximum
+/// coverage where they overlap. Empty bitmaps don't grow the bounds.
+pub(crate) fn union_all(images: &[&SwashImage]) -> SwashImage {
+    let images: Vec<_> = images
+        .iter()
+        .filter(|image| image.placement.width > 0 && image.placement.height > 0)
+        .collect();
+    if images.is_empty() {
+        return SwashImage::new();
+    }
+
+    let left = images
+        .iter()
+        .map(|image| image.placement.left)
+        .min()
+        .unwrap();
+    let top = images
+        .iter()
+        .map(|image| image.placement.top)
+        .max()
+        .unwrap();
+    let right = images
+        .iter()
+        .map(|image| image.placement.left + image.placement.width as i32)
+        .max()
+        .unwrap();
+    let bottom = images
+        .iter()
+        .map(|image| image.placement.top - image.placement.height as i32)
+        .min()
+        .unwrap();
+
+    let mut image = SwashImage::new();
+    image.placement = Placement {
+        left,
+        top,
+        width: (right - left) as u32,
+        height: (top - bottom) as u32,
+    };
+    image.data = vec![0; image.placement.width as usize * image.placement.height as usize];
+
+    for source in images {
+        let width = source.placement.width as usize;
+        let column = (source.placement.left - left) as usize;
+        let first_row = (top - source.placement.top) as usize;
+
+        for (row, coverage) in source.data.chunks_exact(width).enumerate() {
+            let start = (first_row + row) * image.placement.width as usize + column;
+            for (target, alpha) in image.data[start..start + width].iter_mut().zip(coverage) {
+                *target = (*target).max(*alpha);
+            }
+        }
+    }
+
+    image
+}
+
+pub(crate) fn blur(mut bitmap: SwashImage, radius: f32) -> SwashImage {
+    let padding = radius.ceil() as usize;
+    let glyph_width = bitmap.placement.width as usize;
+    let glyph_height = bitmap.placement.height as usize;
+
+    if padding == 0 || glyph_width == 0 || glyph_height == 0 {
+        return bitmap;
+    }
+
+    let sigma = (radius / 2.0).max(0.5);
+    let mut kernel: Vec<f32> = (0..=padding * 2)
+        .map(|index| {
+            let distance = index as f32 - padding as f32;
+            (-(distance * distance) / (2.0 * sigma * sigma)).exp()
+        })
+        .collect();
+    let total: f32 = kernel.iter().sum();
+    kernel.iter_mut().for_each(|weight| *weight /= total);
+
+    let width = glyph_width + padding * 2;
+    let height = glyph_height + padding * 2;
+
+    let mut source = vec![0.0; width * height];
+    for (row, coverage) in bitmap.data.chunks_exact(glyph_width).enumerate() {
+        let start = (row + padding) * width + padding;
+        for (target, alpha) in source[start..start + glyph_width].iter_mut().zip(coverage) {
+            *target = *alpha as f32;
+        }
+    }
+
+    let mut horizontal = vec![0.0; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            horizontal[y * width + x] = kernel
+                .iter()
+                .enumerate()
+                .filter_map(|(index, weight)| {
+                    let sample = (x + index).checked_sub(padding).filter(|x| *x < width)?;
+                    Some(source[y * width + sample] * weight)
+                })
+                .sum();
+        }
+    }
+
+    bitmap.data = (0..width * height)
+        .map(|index| {
+            let (x, y) = (index % width, index / width);
+            let value: f32 = kernel
+                .iter()
+                .enumerate()
+                .filter_map(|(offset, weight)| {
+                    let sample = (y + offset).checked_sub(padding).filter(|y| *y < height)?;
+                    Some(horizontal[sample * width + x] * weight)
+                })
+                .sum();
+
+            value.round().clamp(0.0, 255.0) as u8
+        })
+        .collect();
+
+    bitmap.placement.left -= padding as i32;
+    bitmap.placement.top += padding as i32;
+    bitmap.placement.width = width as u32;
+    bitmap.placement.height = height as u32;
+
+    bitmap
+}
+
+pub(crate) fn offset(bitmap: &SwashImage, distance: f32) -> SwashImage {
+    let padding = distance.max(0.0).ceil() as usize + 1;
+
+    map_signed_distances(bitmap.clone(), padding, |signed_distance| {
+        ((signed_distance + distance + 0.5).clamp(0.0, 1.0) * 255.0).round() as u8
+    })
+}
+
+pub(crate) fn alpha_channel(bitmap: &SwashImage) -> SwashImage {
+    if bitmap.content != Content::Color {
+        return bitmap.clone();
+    }
+
+    let mut image = SwashImage::new();
+    image.placement = bitmap.placement;
+    image.data = bitmap.data.chunks_exact(4).map(|pixel| pixel[3]).collect();
+    image
+}
+
+pub(crate) fn subtract(a: &SwashImage, b: &SwashImage) -> SwashImage {
+    combine(a, b, |a, b| (a as u16 * (255 - b) as u16 / 255) as u8)
+}
+
+pub(crate) fn intersect(a: &SwashImage, b: &SwashImage) -> SwashImage {
+    combine(a, b, |a, b| (a as u16 * b as u16 / 255) as u8)
+}
+
+fn combine(a: &SwashImage, b: &SwashImage, operation: impl Fn(u8, u8) -> u8) -> SwashImage {
+    let mut image = SwashImage::new();
+    image.placement = a.placement;
+
+    let width = a.placement.width as i32;
+    let other_width = b.placement.width as i32;
+    let other_height = b.placement.height as i32;
+
+    image.data = a
+        .data
+        .iter()
+        .enumerate()
+        .map(|(index, alpha)| {
+            let x = index as i32 % width + a.placement.left - b.placement.left;
+            let y = index as i32 / width + b.placement.top - a.placement.top;
+
+            let other = if (0..other_width).contains(&x) && (0..other_height).contains(&y) {
+                b.data[(y * other_width + x) as usize]
+            } else {
+                0
+            };
+
+            operation(*alpha, other)
+        })
+        .collect();
+
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bitmap(left: i32, top: i32, width: u32, data: &[u8]) -> SwashImage {
+        let mut image = SwashImage::new();
+        image.placement = Placement {
+            left,
+            top,
+            width,
+            height: data.len() as u32 / width,
+        };
+        image.data = data.to_vec();
+        image
+    }
+
+    #[test]
+    fn union_covers_both_bitmaps() {
+        let a = bitmap(0, 2, 2, &[10, 20, 30, 40]);
+        let b = bitmap(1, 1, 2, &[100, 5, 6, 7]);
+
+        let image = union(&a, &b);
+
+        let placement = image.placement;
+        assert_eq!(
+            (
+                placement.left,
+                placement.top,
+                placement.width,
+                placement.height
+            ),
+            (0, 2, 3, 3)
+        );
+        #[rustfmt::skip]
+        assert_eq!(image.data, [
+            10, 20, 0,
+            30, 100, 5,
+            0, 6, 7,
+        ]);
+    }
+
+    #[test]
+    fn union_all_ignores_empty_bitmaps() {
+        let a = bitmap(5, 10, 1, &[50]);
+        let b = bitmap(7, 10, 1, &[60]);
+        let empty = SwashImage::new();
+
+        let image = union_all(&[&empty, &a, &b]);
+        assert_eq!(image.placement.left, 5);
+        assert_eq!(image.placement.top, 10);
+        assert_eq!(image.data, [50, 0, 60]);
+
+        assert!(union_all(&[&empty]).data.is_empty());
+    }
+
+    #[test]
+    fn blur_spreads_coverage_symmetrically() {
+        let image = blur(bitmap(3, 4, 1, &[255]), 2.0);
+
+        let placement = image.placement;
+        assert_eq!(
+            (
+                placement.left,
+                placement.top,
+                placement.width,
+                placement.height
+            ),
+            (1, 6, 5, 5)
+        );
+        let at = |x: usize, y: usize| image.data[y * 5 + x];
+        assert!(at(2, 2) > at(1, 2) && at(1, 2) > at(0, 2));
+        assert_eq!(at(1, 2), at(3, 2));
+        assert_eq!(at(2, 1), at(2, 3));
+        assert_eq!(at(1, 2), at(2, 1));
+
+        let total: u32 = image.data.iter().map(|alpha| *alpha as u32).sum();
+        assert!(total.abs_diff(255) <= 12);
+    }
+
+    #[test]
+    fn blur_without_radius_keeps_the_bitmap() {
+        let image = blur(bitmap(0, 1, 2, &[1, 2]), 0.0);
+        assert_eq!(image.placement.width, 2);
+        assert_eq!(image.data, [1, 2]);
+    }
+
+    #[test]
+    fn subtract_and_intersect_use_the_first_bitmap_bounds() {
+        let a = bitmap(0, 2, 2, &[255, 255, 255, 128]);
+        let b = bitmap(1, 1, 2, &[255, 255, 0, 0]);
+
+        let difference = subtract(&a, &b);
+        assert_eq!(difference.placement.width, 2);
+        assert_eq!(difference.data, [255, 255, 255, 0]);
+
+        let overlap = intersect(&a, &b);
+        assert_eq!(overlap.data, [0, 0, 0, 128]);
+    }
+
+    #[test]
+    fn subtract_scales_partial_coverage() {
+        let a = bitmap(0, 1, 1, &[200]);
+        let b = bitmap(0, 1, 1, &[51]);
+
+        assert_eq!(subtract(&a, &b).data, [160]);
+        assert_eq!(intersect(&a, &b).data, [40]);
+    }
+}
@@ -0,0 +1,375 @@
+use bevy::prelude::*;
+use std::ops::Range;
+use swash::zeno::{Command, Fill, Mask, Origin, PathBuilder, Style};
+
+use crate::{
+    clip_outline, layer_z, outline_layer_z, outline_stroke, FontInstance, GlyphAtlas,
+    GlyphAtlasKey, GlyphImage, OutlinedTextSection, SwashImage, TextFill,
+};
+
+const STRETCH_SIZE: f32 = 2.0;
+
+#[derive(Debug, Clone, Default)]
+pub struct TextDecoration {
+    pub color: Option<TextFill>,
+    pub thickness: Option<f32>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BackgroundStyle {
+    pub color: Color,
+    pub padding: Vec2,
+    pub corner_radius: f32,
+}
+
+pub(crate) fn decoration_images(
+    glyph_atlas: &mut GlyphAtlas,
+    images: &mut Assets<Image>,
+    font: &FontInstance,
+    section_index: usize,
+    section: &OutlinedTextSection,
+    span: Range<f32>,
+    scale_factor: f32,
+) -> Vec<GlyphImage> {
+    let metrics = font.metrics;
+    let outlines = section.outline.layers();
+    let mut glyph_images = Vec::new();
+    let width = span.end - span.start;
+
+    let decorations = [
+        (&section.underline, metrics.underline_offset),
+        (&section.strikethrough, metrics.strikeout_offset),
+    ];
+
+    for (decoration, position) in decorations {
+        let Some(decoration) = decoration else {
+            continue;
+        };
+
+        let thickness = decoration
+            .thickness
+            .map_or(metrics.stroke_size, |thickness| thickness / scale_factor)
+            .max(1.0);
+        let center = position - metrics.stroke_size / 2.0;
+        let key = |outline_width| {
+            GlyphAtlasKey::decoration(
+                font.font_ref.key,
+                font.variations_key,
+                thickness,
+                outline_width,
+            )
+        };
+
+        for (index, outline) in outlines.iter().enumerate() {
+            let stroke_width = outline.width / scale_factor;
+            let key = key(stroke_width).stroke_style(outline.stroke_style());
+            let strip_width = 2.0 * (stroke_width.ceil() + STRETCH_SIZE);
+            let path = rectangle(strip_width, thickness);
+
+            glyph_images.extend(stretched_images(
+                glyph_atlas,
+                images,
+                key,
+                section_index,
+                Vec3::new(
+                    span.start,
+                    center,
+                    outline_layer_z(outline, index, outlines.len()),
+                ),
+                &outline.color,
+                Vec2::new(width - strip_width, 0.0),
+                || {
+                    let stroke = path_to_bitmap(&path, outline_stroke(outline, stroke_width));
+                    clip_outline(outline, stroke, || path_to_bitmap(&path, Fill::NonZero))
+                },
+            ));
+        }
+
+        let strip_width = 2.0 * STRETCH_SIZE;
+        glyph_images.extend(stretched_images(
+            glyph_atlas,
+            images,
+            key(0.0),
+            section_index,
+            Vec3::new(span.start, center, layer_z(0)),
+            decoration.color.as_ref().unwrap_or(&section.color),
+            Vec2::new(width - strip_width, 0.0),
+            || path_to_bitmap(&rectangle(strip_width, thickness), Fill::NonZero),
+        ));
+    }
+
+    glyph_images
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn background_images(
+    glyph_atlas: &mut GlyphAtlas,
+    images: &mut Assets<Image>,
+    section_index: usize,
+    background: &BackgroundStyle,
+    rect: Rect,
+    z: f32,
+    scale_factor: f32,
+) -> Vec<GlyphImage> {
+    let padding = background.padding / scale_factor;
+    let corner_radius = (background.corner_radius / scale_factor).max(0.0);
+    let size = rect.size() + padding * 2.0;
+    let tile_size = 2.0 * (corner_radius.ceil() + STRETCH_SIZE);
+
+    stretched_images(
+        glyph_atlas,
+        images,
+        GlyphAtlasKey::background(corner_radius),
+        section_index,
+        (rect.min - padding).extend(z),
+        &TextFill::Solid(background.color),
+        size - tile_size,
+        || {
+            let mut path = Vec::new();
+            path.add_round_rect(
+                [0.0, 0.0],
+                tile_size,
+                tile_size,
+                corner_radius,
+                corner_radius,
+            );
+            path_to_bitmap(&path, Fill::NonZero)
+        },
+    )
+}
+
+/// Places a small rasterized tile and stretches its middle `STRETCH_SIZE`
+/// pixels by `stretch`, keeping the edges and corners at their native size.
+/// Tiles that need to shrink are scaled down as a whole instead.
+#[allow(clippy::too_many_arguments)]
+fn stretched_images(
+    glyph_atlas: &mut GlyphAtlas,
+    images: &mut Assets<Image>,
+    key: GlyphAtlasKey,
+    section_index: usize,
+    origin: Vec3,
+    fill: &TextFill,
+    stretch: Vec2,
+    rasterize: impl FnOnce() -> SwashImage,
+) -> Vec<GlyphImage> {
+    let Some(entry) = glyph_atlas.acquire(key, images, rasterize) else {
+        return Vec::new();
+    };
+    let tile = GlyphImage::new(key, section_index, origin, fill, entry, 1.0);
+
+    let columns = slices(tile.rect.width(), stretch.x);
+    let rows = slices(tile.rect.height(), stretch.y);
+    let height: f32 = rows.iter().map(|(_, length)| length).sum();
+    let top = tile.offset_y + height;
+
+    let mut glyph_images = Vec::with_capacity(columns.len() * rows.len());
+    let mut y = top;
+    for (row, row_height) in &rows {
+        y -= row_height;
+
+        let mut x = tile.offset_x;
+        for (column, column_width) in &columns {
+            if !glyph_images.is_empty() {
+                glyph_atlas.acquire(key, images, SwashImage::new);
+            }
+
+            glyph_images.push(GlyphImage {
+                offset_x: x,
+                offset_y: y,
+                size: Vec2::new(*column_width, *row_height),
+                rect: Rect::new(
+                    tile.rect.min.x + column.start,
+                    tile.rect.min.y + row.start,
+                    tile.rect.min.x + column.end,
+                    tile.rect.min.y + row.end,
+                ),
+                ..tile.clone()
+            });
+            x += column_width;
+        }
+    }
+
+    glyph_images
+}
+
+/// Splits a tile edge of `length` pixels into source ranges and the lengths
+/// they are drawn at.
+fn slices(length: f32, stretch: f32) -> Vec<(Range<f32>, f32)> {
+    if stretch <= 0.0 || length <= STRETCH_SIZE {
+        return vec![(0.0..length, (length + stretch).max(0.0))];
+    }
+
+    let start = ((length - STRETCH_SIZE) / 2.0).floor();
+    let end = start + STRETCH_SIZE;
+
+    [
+        (0.0..start, start),
+        (start..end, STRETCH_SIZE + stretch),
+        (end..length, length - end),
+    ]
+    .into_iter()
+    .filter(|(range, _)| !range.is_empty())
+    .collect()
+}
+
+fn rectangle(width: f32, thickness: f32) -> Vec<Command> {
+    let mut path = Vec::new();
+    path.add_rect([0.0, -thickness / 2.0], width, thickness);
+    path
+}
+
+fn path_to_bitmap<'a>(path: &[Command], style: impl Into<Style<'a>>) -> SwashImage {
+    let (data, placement) = Mask::new(path)
+        .style(style)
+        .origin(Origin::BottomLeft)
+        .render();
+
+    let mut image = SwashImage::new();
+    image.data = data;
+    image.placement = placement;
+    // Auto-sized zeno masks report the bottom row as `top` for
+    // `Origin::BottomLeft`; glyph images expect the top row.
+    image.placement.top += placement.height as i32;
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glyph_sprite;
+    use swash::FontRef;
+
+    #[test]
+    fn slices_stretch_the_middle() {
+        assert_eq!(
+            slices(10.0, 5.0),
+            [(0.0..4.0, 4.0), (4.0..6.0, 7.0), (6.0..10.0, 4.0)]
+        );
+        assert_eq!(slices(10.0, 0.0), [(0.0..10.0, 10.0)]);
+        assert_eq!(slices(10.0, -4.0), [(0.0..10.0, 6.0)]);
+        assert_eq!(slices(2.0, 5.0), [(0.0..2.0, 7.0)]);
+    }
+
+    fn rounded_background(padding: Vec2) -> BackgroundStyle {
+        BackgroundStyle {
+            color: Color::WHITE,
+            padding,
+            corner_radius: 4.0,
+        }
+    }
+
+    /// Bounds of the sprites the glyph images are extracted as, checked to
+    /// span `x` plus the empty column of padding masks carry on either side.
+    fn padded_bounds(glyph_images: &[GlyphImage], x: Range<f32>) -> Rect {
+        let bounds = glyph_images
+            .iter()
+            .map(|glyph_image| {
+                let sprite =
+                    glyph_sprite(glyph_image, &GlobalTransform::IDENTITY, Entity::PLACEHOLDER);
+                let min = sprite.transform.translation().truncate();
+                Rect::from_corners(min, min + sprite.custom_size.unwrap())
+            })
+            .reduce(|a, b| a.union(b))
+            .unwrap();
+        assert_eq!(bounds.min.x..bounds.max.x, x.start - 1.0..x.end + 1.0);
+        bounds
+    }
+
+    #[test]
+    fn backgrounds_share_one_nine_slice_tile() {
+        let mut glyph_atlas = GlyphAtlas::default();
+        let mut images = Assets::<Image>::default();
+        let background = rounded_background(Vec2::ZERO);
+
+        let mut glyph_images = Vec::new();
+        for rect in [
+            Rect::new(0.0, 0.0, 100.0, 20.0),
+            Rect::new(10.0, -5.0, 250.0, 40.0),
+        ] {
+            let slices = background_images(
+                &mut glyph_atlas,
+                &mut images,
+                0,
+                &background,
+                rect,
+                0.0,
+                1.0,
+            );
+            assert_eq!(slices.len(), 9);
+
+            let bounds = padded_bounds(&slices, rect.min.x..rect.max.x);
+            assert_eq!(bounds.min.y..bounds.max.y, rect.min.y..rect.max.y);
+
+            glyph_images.extend(slices);
+        }
+
+        // The same corner radius at twice the scale factor reuses the tile.
+        let scaled = BackgroundStyle {
+            corner_radius: 8.0,
+            ..background
+        };
+        glyph_images.extend(background_images(
+            &mut glyph_atlas,
+            &mut images,
+            0,
+            &scaled,
+            Rect::new(0.0, 0.0, 50.0, 10.0),
+            0.0,
+            2.0,
+        ));
+
+        let statistics = glyph_atlas.statistics();
+        assert_eq!(statistics.live_glyphs, 1);
+
+        for glyph_image in &glyph_images {
+            glyph_atlas.release(&glyph_image.key);
+        }
+        assert_eq!(glyph_atlas.statistics().stale_glyphs, 1);
+    }
+
+    #[test]
+    fn background_sprites_cover_the_box() {
+        let mut glyph_atlas = GlyphAtlas::default();
+        let mut images = Assets::<Image>::default();
+        let background = rounded_background(Vec2::new(4.0, 2.0));
+
+        let glyph_images = background_images(
+            &mut glyph_atlas,
+            &mut images,
+            0,
+            &background,
+            Rect::new(0.0, 0.0, 100.0, 20.0),
+            0.0,
+            1.0,
+        );
+
+        let bounds = padded_bounds(&glyph_images, -4.0..104.0);
+        assert_eq!(bounds.min.y..bounds.max.y, -2.0..22.0);
+    }
+
+    #[test]
+    fn solid_underlines_cover_the_span() {
+        let data = include_bytes!("../assets/fonts/Montserrat-Regular.ttf");
+        let font = FontInstance::new(FontRef::from_index(data, 0).unwrap(), &[], 20.0);
+        let section = OutlinedTextSection {
+            underline: Some(TextDecoration::default()),
+            ..default()
+        };
+        let mut glyph_atlas = GlyphAtlas::default();
+        let mut images = Assets::<Image>::default();
+
+        for span in [10.0..210.0, -40.0..0.0] {
+            let glyph_images = decoration_images(
+                &mut glyph_atlas,
+                &mut images,
+                &font,
+                0,
+                &section,
+                span.clone(),
+                1.0,
+            );
+
+            padded_bounds(&glyph_images, span);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use bevy::prelude::*;
+use bevy::render::mesh::MeshVertexBufferLayoutRef;
+use bevy::render::render_resource::{
+    AsBindGroup, RenderPipelineDescriptor, ShaderRef, SpecializedMeshPipelineError,
+};
+use bevy::sprite::{Material2d, Material2dKey};
+
+pub(crate) const TEXT_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(0x1c84_9e27_d35a_4f60_a2b9_0e7d_46c3_f158);
+pub(crate) const OUTLINED_TEXT_FILL_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(0x8a61_f0c2_3b9d_4e75_9f14_c6e8_25b7_0d93);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TextFill {
+    Solid(Color),
+    LinearGradient {
+        from: Color,
+        to: Color,
+        angle: f32,
+        mapping: TextFillMapping,
+    },
+    RadialGradient {
+        from: Color,
+        to: Color,
+        center: Vec2,
+        radius: f32,
+        mapping: TextFillMapping,
+    },
+    Texture {
+        image: Handle<Image>,
+        tint: Color,
+        mapping: TextFillMapping,
+    },
+}
+
+impl TextFill {
+    pub(crate) fn solid_color(&self) -> Option<LinearRgba> {
+        match self {
+            TextFill::Solid(color) => Some(color.to_linear()),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn mapping(&self) -> TextFillMapping {
+        match self {
+            TextFill::Solid(_) => TextFillMapping::Block,
+            TextFill::LinearGradient { mapping, .. }
+            | TextFill::RadialGradient { mapping, .. }
+            | TextFill::Texture { mapping, .. } => *mapping,
+        }
+    }
+
+    pub(crate) fn uniform(&self) -> TextFillUniform {
+        match self {
+            TextFill::Solid(color) => TextFillUniform {
+                kind: 0,
+                from: color.to_linear(),
+                to: color.to_linear(),
+                gradient: Vec4::ZERO,
+            },
+            TextFill::LinearGradient {
+                from, to, angle, ..
+            } => TextFillUniform {
+                kind: 1,
+                from: from.to_linear(),
+                to: to.to_linear(),
+                gradient: Vec4::new(angle.cos(), -angle.sin(), 0.0, 0.0),
+            },
+            TextFill::RadialGradient {
+                from,
+                to,
+                center,
+                radius,
+                ..
+            } => TextFillUniform {
+                kind: 2,
+                from: from.to_linear(),
+                to: to.to_linear(),
+                gradient: center.extend(*radius).extend(0.0),
+            },
+            TextFill::Texture { tint, .. } => TextFillUniform {
+                kind: 3,
+                from: tint.to_linear(),
+                to: tint.to_linear(),
+                gradient: Vec4::ZERO,
+            },
+        }
+    }
+
+    pub(crate) fn texture(&self) -> Option<Handle<Image>> {
+        match self {
+            TextFill::Texture { image, .. } => Some(image.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for TextFill {
+    fn default() -> Self {
+        Self::Solid(Color::default())
+    }
+}
+
+impl From<Color> for TextFill {
+    fn from(color: Color) -> Self {
+        Self::Solid(color)
+    }
+}
+
+impl From<Srgba> for TextFill {
+    fn from(color: Srgba) -> Self {
+        Self::Solid(color.into())
+    }
+}
+
+impl From<LinearRgba> for TextFill {
+    fn from(color: LinearRgba) -> Self {
+        Self::Solid(color.into())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TextFillMapping {
+    #[default]
+    Block,
+    Glyph,
+}
+
+pub(crate) struct TextFillUniform {
+    pub(crate) kind: u32,
+    pub(crate) from: LinearRgba,
+    pub(crate) to: LinearRgba,
+    pub(crate) gradient: Vec4,
+}
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct OutlinedTextFillMaterial {
+    #[uniform(0)]
+    pub fill_kind: u32,
+    #[uniform(0)]
+    pub fill_from: LinearRgba,
+    #[uniform(0)]
+    pub fill_to: LinearRgba,
+    #[uniform(0)]
+    pub fill_gradient: Vec4,
+    #[texture(1)]
+    #[sampler(2)]
+    pub texture: Handle<Image>,
+    #[texture(3)]
+    #[sampler(4)]
+    pub fill_texture: Option<Handle<Image>>,
+}
+
+impl OutlinedTextFillMaterial {
+    pub fn new(fill: &TextFill, texture: Handle<Image>) -> Self {
+        let uniform = fill.uniform();
+
+        Self {
+            fill_kind: uniform.kind,
+            fill_from: uniform.from,
+            fill_to: uniform.to,
+            fill_gradient: uniform.gradient,
+            texture,
+            fill_texture: fill.texture(),
+        }
+    }
+}
+
+impl Material2d for OutlinedTextFillMaterial {
+    fn vertex_shader() -> ShaderRef {
+        OUTLINED_TEXT_FILL_SHADER_HANDLE.into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        OUTLINED_TEXT_FILL_SHADER_HANDLE.into()
+    }
+
+    fn specialize(
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayoutRef,
+        _key: Material2dKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        specialize_text_vertex(descriptor, layout)
+    }
+}
+
+pub(crate) fn specialize_text_vertex(
+    descriptor: &mut RenderPipelineDescriptor,
+    layout: &MeshVertexBufferLayoutRef,
+) -> Result<(), SpecializedMeshPipelineError> {
+    let vertex_layout = layout.0.get_layout(&[
+        Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+        Mesh::ATTRIBUTE_UV_0.at_shader_location(1),
+        Mesh::ATTRIBUTE_UV_1.at_shader_location(2),
+        Mesh::ATTRIBUTE_COLOR.at_shader_location(3),
+    ])?;
+    descriptor.vertex.buffers = vec![vertex_layout];
+    Ok(())
+}
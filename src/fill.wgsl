@@ -0,0 +1,20 @@
+#import bevy_swash::text::{TextFill, Vertex, VertexOutput, fill_color, text_vertex}
+
+@group(2) @binding(0) var<uniform> fill: TextFill;
+@group(2) @binding(1) var coverage_texture: texture_2d<f32>;
+@group(2) @binding(2) var coverage_sampler: sampler;
+@group(2) @binding(3) var fill_texture: texture_2d<f32>;
+@group(2) @binding(4) var fill_sampler: sampler;
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    return text_vertex(vertex);
+}
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(coverage_texture, coverage_sampler, mesh.uv);
+    let color = fill_color(fill, mesh.fill_uv, fill_texture, fill_sampler) * mesh.color;
+
+    return vec4<f32>(color.rgb * coverage.rgb, color.a * coverage.a);
+}
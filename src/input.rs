@@ -0,0 +1,850 @@
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use std::ops::Range;
+use swash::text::cluster::{Boundary, CharCluster, CharInfo, Parser, Token};
+use swash::text::{analyze, Script};
+
+use crate::{layer_z, OutlinedText, OutlinedTextLayout, SECTION_BACKGROUND_LAYER};
+
+const CARET_Z: f32 = 0.01;
+
+#[derive(Component, Clone, Debug)]
+pub struct OutlinedTextInput {
+    pub section: usize,
+    pub multiline: bool,
+    pub focused: bool,
+    pub caret: usize,
+    pub anchor: usize,
+    pub caret_color: Color,
+    pub caret_width: f32,
+    pub selection_color: Color,
+    pub blink_interval: f32,
+}
+
+impl OutlinedTextInput {
+    pub fn selection(&self) -> Range<usize> {
+        self.caret.min(self.anchor)..self.caret.max(self.anchor)
+    }
+
+    pub fn has_selection(&self) -> bool {
+        self.caret != self.anchor
+    }
+
+    fn move_caret(&mut self, caret: usize, extend: bool) {
+        self.caret = caret;
+        if !extend {
+            self.anchor = caret;
+        }
+    }
+
+    fn replace_selection(&mut self, value: &mut String, replacement: &str) {
+        let selection = self.selection();
+        value.replace_range(selection.clone(), replacement);
+        self.move_caret(selection.start + replacement.len(), false);
+    }
+
+    fn delete(&mut self, value: &mut String, target: usize) {
+        if !self.has_selection() {
+            self.anchor = target;
+        }
+        self.replace_selection(value, "");
+    }
+}
+
+impl Default for OutlinedTextInput {
+    fn default() -> Self {
+        Self {
+            section: 0,
+            multiline: false,
+            focused: false,
+            caret: 0,
+            anchor: 0,
+            caret_color: Color::WHITE,
+            caret_width: 2.0,
+            selection_color: Color::srgba(0.3, 0.5, 1.0, 0.4),
+            blink_interval: 0.5,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct OutlinedTextInputVisual;
+
+#[derive(Component)]
+pub struct OutlinedTextCaret {
+    shown_at: f32,
+    interval: f32,
+}
+
+enum Movement {
+    Grapheme,
+    Word,
+}
+
+/// Edits the focused inputs from [`KeyboardInput`] events. Typed text comes from
+/// their `logical_key` rather than `ReceivedCharacter`, which is deprecated and
+/// reports the same characters a second time.
+pub fn edit_text_input(
+    mut keyboard_input: EventReader<KeyboardInput>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut inputs: Query<(
+        &mut OutlinedTextInput,
+        &mut OutlinedText,
+        Option<&OutlinedTextLayout>,
+    )>,
+) {
+    let events: Vec<&KeyboardInput> = keyboard_input
+        .read()
+        .filter(|event| event.state == ButtonState::Pressed)
+        .collect();
+    if events.is_empty() {
+        return;
+    }
+
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let control = keys.any_pressed([
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::SuperLeft,
+        KeyCode::SuperRight,
+    ]);
+    let alt = keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+    // AltGr arrives as AltRight, and on Windows together with ControlLeft.
+    let alt_graph = keys.pressed(KeyCode::AltRight) || (control && alt);
+    let movement = if control || alt {
+        Movement::Word
+    } else {
+        Movement::Grapheme
+    };
+
+    for (mut input, mut text, layout) in inputs.iter_mut() {
+        if !input.focused || input.section >= text.sections.len() {
+            continue;
+        }
+
+        // The app may have replaced the value since the caret was placed.
+        let section = input.section;
+        let value = &text.sections[section].value;
+        let caret = char_boundary(value, input.caret);
+        let anchor = char_boundary(value, input.anchor);
+        if (caret, anchor) != (input.caret, input.anchor) {
+            input.caret = caret;
+            input.anchor = anchor;
+        }
+
+        for event in &events {
+            let value = &text.sections[section].value;
+            let caret = input.caret;
+
+            match &event.logical_key {
+                Key::Character(character)
+                    if control && !alt_graph && character.eq_ignore_ascii_case("a") =>
+                {
+                    input.anchor = 0;
+                    input.caret = value.len();
+                }
+                Key::Character(character) if alt_graph || (!control && !alt) => {
+                    let value = &mut text.sections[section].value;
+                    input.replace_selection(value, character);
+                }
+                Key::Space => {
+                    let value = &mut text.sections[section].value;
+                    input.replace_selection(value, " ");
+                }
+                Key::Enter if input.multiline => {
+                    let value = &mut text.sections[section].value;
+                    input.replace_selection(value, "\n");
+                }
+                Key::Backspace => {
+                    let target = previous_boundary(value, caret, &movement);
+                    input.delete(&mut text.sections[section].value, target);
+                }
+                Key::Delete => {
+                    let target = next_boundary(value, caret, &movement);
+                    input.delete(&mut text.sections[section].value, target);
+                }
+                Key::ArrowLeft => {
+                    let caret = if input.has_selection() && !shift {
+                        input.selection().start
+                    } else {
+                        previous_boundary(value, caret, &movement)
+                    };
+                    input.move_caret(caret, shift);
+                }
+                Key::ArrowRight => {
+                    let caret = if input.has_selection() && !shift {
+                        input.selection().end
+                    } else {
+                        next_boundary(value, caret, &movement)
+                    };
+                    input.move_caret(caret, shift);
+                }
+                Key::ArrowUp | Key::ArrowDown => {
+                    let down = event.logical_key == Key::ArrowDown;
+                    let caret = layout
+                        .and_then(|layout| vertical_caret(layout, section, caret, down))
+                        .unwrap_or(if down { value.len() } else { 0 });
+                    input.move_caret(caret, shift);
+                }
+                Key::Home => {
+                    let caret = layout
+                        .and_then(|layout| line_boundary(layout, section, caret, false))
+                        .unwrap_or_else(|| value[..caret].rfind('\n').map_or(0, |index| index + 1));
+                    input.move_caret(caret, shift);
+                }
+                Key::End => {
+                    let caret = layout
+                        .and_then(|layout| line_boundary(layout, section, caret, true))
+                        .unwrap_or_else(|| {
+                            value[caret..]
+                                .find('\n')
+                                .map_or(value.len(), |index| caret + index)
+                        });
+                    input.move_caret(caret, shift);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn update_text_input_visuals(
+    mut commands: Commands,
+    time: Res<Time>,
+    inputs: Query<
+        (
+            Entity,
+            &OutlinedTextInput,
+            &OutlinedText,
+            &OutlinedTextLayout,
+            Option<&Children>,
+        ),
+        Or<(Changed<OutlinedTextInput>, Changed<OutlinedTextLayout>)>,
+    >,
+    mut removed: RemovedComponents<OutlinedTextInput>,
+    children: Query<&Children>,
+    visuals: Query<(), With<OutlinedTextInputVisual>>,
+) {
+    let despawn_visuals = |commands: &mut Commands, entity_children: &Children| {
+        for child in entity_children.iter() {
+            if visuals.contains(*child) {
+                commands.entity(*child).despawn_recursive();
+            }
+        }
+    };
+
+    for entity in removed.read() {
+        if let Ok(entity_children) = children.get(entity) {
+            despawn_visuals(&mut commands, entity_children);
+        }
+    }
+
+    for (entity, input, text, layout, entity_children) in inputs.iter() {
+        if let Some(entity_children) = entity_children {
+            despawn_visuals(&mut commands, entity_children);
+        }
+
+        if !input.focused || input.section >= text.sections.len() {
+            continue;
+        }
+
+        let value = &text.sections[input.section].value;
+        let caret_rect = layout.caret_rect(input.section, input.caret).or_else(|| {
+            let line = value[..char_boundary(value, input.caret)]
+                .matches('\n')
+                .count();
+            let line = layout.lines.get(line).or(layout.lines.last())?;
+            Some(Rect::new(
+                line.left,
+                line.baseline - line.descent,
+                line.left,
+                line.baseline + line.ascent,
+            ))
+        });
+
+        commands.entity(entity).with_children(|parent| {
+            if input.has_selection() {
+                for rect in layout.selection_rects(input.section, input.selection()) {
+                    parent.spawn((
+                        SpriteBundle {
+                            sprite: Sprite {
+                                color: input.selection_color,
+                                custom_size: Some(rect.size()),
+                                ..default()
+                            },
+                            transform: Transform::from_translation(
+                                rect.center().extend(layer_z(SECTION_BACKGROUND_LAYER - 1)),
+                            ),
+                            ..default()
+                        },
+                        OutlinedTextInputVisual,
+                    ));
+                }
+            }
+
+            if let Some(rect) = caret_rect {
+                parent.spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: input.caret_color,
+                            custom_size: Some(Vec2::new(input.caret_width, rect.height())),
+                            ..default()
+                        },
+                        transform: Transform::from_translation(rect.center().extend(CARET_Z)),
+                        ..default()
+                    },
+                    OutlinedTextInputVisual,
+                    OutlinedTextCaret {
+                        shown_at: time.elapsed_seconds(),
+                        interval: input.blink_interval,
+                    },
+                ));
+            }
+        });
+    }
+}
+
+pub fn blink_text_input_carets(
+    time: Res<Time>,
+    mut carets: Query<(&OutlinedTextCaret, &mut Visibility)>,
+) {
+    for (caret, mut visibility) in carets.iter_mut() {
+        let visible = caret.interval <= 0.0
+            || (((time.elapsed_seconds() - caret.shown_at) / caret.interval) as u64) % 2 == 0;
+        let target = if visible {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+
+        if *visibility != target {
+            *visibility = target;
+        }
+    }
+}
+
+fn caret_line(layout: &OutlinedTextLayout, section: usize, caret: usize) -> Option<(usize, Rect)> {
+    let rect = layout.caret_rect(section, caret)?;
+    let center = rect.center();
+    let line = layout
+        .lines
+        .iter()
+        .position(|line| center.y >= line.baseline - line.descent)?;
+    Some((line, rect))
+}
+
+fn line_boundary(
+    layout: &OutlinedTextLayout,
+    section: usize,
+    caret: usize,
+    end: bool,
+) -> Option<usize> {
+    let (line, _) = caret_line(layout, section, caret)?;
+    let glyphs = layout.glyphs[layout.lines[line].glyphs.clone()]
+        .iter()
+        .filter(|glyph| glyph.section == section);
+
+    if end {
+        glyphs.map(|glyph| glyph.byte_range.end).max()
+    } else {
+        glyphs.map(|glyph| glyph.byte_range.start).min()
+    }
+}
+
+fn vertical_caret(
+    layout: &OutlinedTextLayout,
+    section: usize,
+    caret: usize,
+    down: bool,
+) -> Option<usize> {
+    let (line, rect) = caret_line(layout, section, caret)?;
+    let target = if down {
+        layout.lines.get(line + 1)?
+    } else {
+        layout.lines.get(line.checked_sub(1)?)?
+    };
+
+    let hit = layout.hit_test(Vec2::new(rect.min.x, target.baseline))?;
+    (hit.section == section).then(|| hit.caret_offset())
+}
+
+/// Clamps `index` into `value`, moving it back to the start of the character
+/// it falls inside.
+fn char_boundary(value: &str, index: usize) -> usize {
+    let mut index = index.min(value.len());
+    while !value.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn tokens(value: &str) -> impl Iterator<Item = Token> + Clone + '_ {
+    value.char_indices().zip(analyze(value.chars())).map(
+        |((offset, ch), (properties, boundary))| Token {
+            ch,
+            offset: offset as u32,
+            len: ch.len_utf8() as u8,
+            info: CharInfo::new(properties, boundary),
+            data: 0,
+        },
+    )
+}
+
+fn grapheme_boundaries(value: &str) -> Vec<usize> {
+    let mut boundaries = vec![0];
+    let mut parser = Parser::new(Script::Latin, tokens(value));
+    let mut cluster = CharCluster::new();
+
+    while parser.next(&mut cluster) {
+        boundaries.push(cluster.range().end as usize);
+    }
+
+    boundaries
+}
+
+fn word_boundaries(value: &str) -> Vec<usize> {
+    let mut boundaries: Vec<usize> = tokens(value)
+        .filter(|token| token.info.boundary() >= Boundary::Word)
+        .map(|token| token.offset as usize)
+        .collect();
+    boundaries.insert(0, 0);
+    boundaries.push(value.len());
+    boundaries.dedup();
+    boundaries
+}
+
+fn previous_boundary(value: &str, caret: usize, movement: &Movement) -> usize {
+    match movement {
+        Movement::Grapheme => grapheme_boundaries(value)
+            .into_iter()
+            .rev()
+            .find(|boundary| *boundary < caret),
+        Movement::Word => word_boundaries(value)
+            .into_iter()
+            .rev()
+            .filter(|boundary| *boundary < caret)
+            .find(|boundary| !value[*boundary..].starts_with(char::is_whitespace)),
+    }
+    .unwrap_or(0)
+}
+
+fn next_boundary(value: &str, caret: usize, movement: &Movement) -> usize {
+    match movement {
+        Movement::Grapheme => grapheme_boundaries(value)
+            .into_iter()
+            .find(|boundary| *boundary > caret),
+        Movement::Word => word_boundaries(value)
+            .into_iter()
+            .filter(|boundary| *boundary > caret)
+            .find(|boundary| !value[..*boundary].ends_with(char::is_whitespace)),
+    }
+    .unwrap_or(value.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{OutlinedTextLayoutGlyph, OutlinedTextLayoutLine, OutlinedTextSection};
+    use bevy::input::keyboard::NativeKey;
+    use bevy::input::InputPlugin;
+
+    fn text(value: &str) -> OutlinedText {
+        OutlinedText {
+            sections: vec![OutlinedTextSection {
+                value: value.to_string(),
+                ..default()
+            }],
+            ..default()
+        }
+    }
+
+    /// Spawns `input` focused, over a single section holding `value`.
+    fn setup(value: &str, input: OutlinedTextInput) -> (App, Entity) {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, InputPlugin))
+            .add_systems(Update, edit_text_input);
+
+        let input = OutlinedTextInput {
+            focused: true,
+            ..input
+        };
+        let entity = app.world_mut().spawn((text(value), input)).id();
+        (app, entity)
+    }
+
+    /// Lays `value` out with 10 unit wide glyphs, breaking it at `breaks`.
+    fn monospace_layout(value: &str, breaks: &[usize]) -> OutlinedTextLayout {
+        let mut layout = OutlinedTextLayout::default();
+        let mut starts = vec![0];
+        starts.extend_from_slice(breaks);
+
+        for (line, &start) in starts.iter().enumerate() {
+            let end = starts.get(line + 1).map_or(value.len(), |end| end - 1);
+            let baseline = -20.0 * line as f32;
+            let first = layout.glyphs.len();
+
+            for (column, (offset, ch)) in value[start..end].char_indices().enumerate() {
+                let offset = start + offset;
+                layout.glyphs.push(OutlinedTextLayoutGlyph {
+                    position: Vec2::new(10.0 * column as f32, baseline),
+                    advance: 10.0,
+                    line,
+                    section: 0,
+                    byte_range: offset..offset + ch.len_utf8(),
+                    rtl: false,
+                });
+            }
+
+            layout.lines.push(OutlinedTextLayoutLine {
+                baseline,
+                left: 0.0,
+                width: 10.0 * (end - start) as f32,
+                ascent: 15.0,
+                descent: 5.0,
+                glyphs: first..layout.glyphs.len(),
+            });
+        }
+
+        layout
+    }
+
+    fn send(app: &mut App, key_code: KeyCode, logical_key: Key, state: ButtonState) {
+        app.world_mut().send_event(KeyboardInput {
+            key_code,
+            logical_key,
+            state,
+            window: Entity::PLACEHOLDER,
+        });
+    }
+
+    fn press(app: &mut App, key_code: KeyCode, logical_key: Key) {
+        send(app, key_code, logical_key.clone(), ButtonState::Pressed);
+        app.update();
+        send(app, key_code, logical_key, ButtonState::Released);
+    }
+
+    fn press_with(app: &mut App, modifier: KeyCode, key_code: KeyCode, logical_key: Key) {
+        send(
+            app,
+            modifier,
+            Key::Unidentified(NativeKey::Unidentified),
+            ButtonState::Pressed,
+        );
+        press(app, key_code, logical_key);
+        send(
+            app,
+            modifier,
+            Key::Unidentified(NativeKey::Unidentified),
+            ButtonState::Released,
+        );
+        app.update();
+    }
+
+    fn type_text(app: &mut App, value: &str) {
+        for ch in value.chars() {
+            if ch == ' ' {
+                press(app, KeyCode::Space, Key::Space);
+            } else {
+                press(app, KeyCode::KeyA, Key::Character(ch.to_string().into()));
+            }
+        }
+    }
+
+    fn state(app: &App, entity: Entity) -> (String, usize, usize) {
+        let world = app.world();
+        let input = world.get::<OutlinedTextInput>(entity).unwrap();
+        let text = world.get::<OutlinedText>(entity).unwrap();
+        (text.sections[0].value.clone(), input.caret, input.anchor)
+    }
+
+    #[test]
+    fn typing_inserts_at_the_caret() {
+        let (mut app, entity) = setup("", default());
+        type_text(&mut app, "hé llo");
+        assert_eq!(state(&app, entity), ("hé llo".to_string(), 7, 7));
+
+        press(&mut app, KeyCode::ArrowLeft, Key::ArrowLeft);
+        type_text(&mut app, "x");
+        assert_eq!(state(&app, entity), ("hé llxo".to_string(), 7, 7));
+    }
+
+    #[test]
+    fn only_focused_inputs_are_edited() {
+        let (mut app, focused) = setup("", default());
+        let unfocused = app
+            .world_mut()
+            .spawn((text(""), OutlinedTextInput::default()))
+            .id();
+
+        type_text(&mut app, "hi");
+        assert_eq!(state(&app, focused).0, "hi");
+        assert_eq!(state(&app, unfocused).0, "");
+    }
+
+    #[test]
+    fn modified_characters_are_not_inserted() {
+        let (mut app, entity) = setup("abc", default());
+        press_with(
+            &mut app,
+            KeyCode::AltLeft,
+            KeyCode::KeyX,
+            Key::Character("x".into()),
+        );
+        press_with(
+            &mut app,
+            KeyCode::ControlLeft,
+            KeyCode::KeyX,
+            Key::Character("x".into()),
+        );
+        assert_eq!(state(&app, entity).0, "abc");
+
+        press_with(
+            &mut app,
+            KeyCode::ControlLeft,
+            KeyCode::KeyA,
+            Key::Character("a".into()),
+        );
+        assert_eq!(state(&app, entity), ("abc".to_string(), 3, 0));
+    }
+
+    #[test]
+    fn alt_graph_characters_are_inserted() {
+        let (mut app, entity) = setup("", default());
+        press_with(
+            &mut app,
+            KeyCode::AltRight,
+            KeyCode::KeyQ,
+            Key::Character("@".into()),
+        );
+        assert_eq!(state(&app, entity).0, "@");
+
+        // Windows reports AltGr as ControlLeft and AltRight together.
+        send(
+            &mut app,
+            KeyCode::ControlLeft,
+            Key::Control,
+            ButtonState::Pressed,
+        );
+        press_with(
+            &mut app,
+            KeyCode::AltRight,
+            KeyCode::KeyE,
+            Key::Character("€".into()),
+        );
+        assert_eq!(state(&app, entity).0, "@€");
+    }
+
+    #[test]
+    fn backspace_and_delete_remove_graphemes() {
+        let input = OutlinedTextInput {
+            caret: 3,
+            anchor: 3,
+            ..default()
+        };
+        let (mut app, entity) = setup("aéb", input);
+
+        press(&mut app, KeyCode::Backspace, Key::Backspace);
+        assert_eq!(state(&app, entity), ("ab".to_string(), 1, 1));
+
+        press(&mut app, KeyCode::Delete, Key::Delete);
+        assert_eq!(state(&app, entity), ("a".to_string(), 1, 1));
+
+        press(&mut app, KeyCode::Delete, Key::Delete);
+        assert_eq!(state(&app, entity), ("a".to_string(), 1, 1));
+    }
+
+    #[test]
+    fn stale_carets_are_clamped_to_the_value() {
+        let input = OutlinedTextInput {
+            caret: 10,
+            anchor: 2,
+            ..default()
+        };
+        let (mut app, entity) = setup("aéb", input);
+
+        // The anchor sits inside `é`, the caret past the end.
+        press(&mut app, KeyCode::Home, Key::Home);
+        assert_eq!(state(&app, entity), ("aéb".to_string(), 0, 0));
+
+        app.world_mut()
+            .get_mut::<OutlinedText>(entity)
+            .unwrap()
+            .sections[0]
+            .value = "é".to_string();
+        let mut input = app
+            .world_mut()
+            .get_mut::<OutlinedTextInput>(entity)
+            .unwrap();
+        input.caret = 1;
+        input.anchor = 1;
+        press_with(&mut app, KeyCode::ShiftLeft, KeyCode::End, Key::End);
+        assert_eq!(state(&app, entity), ("é".to_string(), 2, 0));
+    }
+
+    #[test]
+    fn shift_extends_the_selection() {
+        let (mut app, entity) = setup("hello", default());
+
+        press_with(
+            &mut app,
+            KeyCode::ShiftLeft,
+            KeyCode::ArrowRight,
+            Key::ArrowRight,
+        );
+        press_with(
+            &mut app,
+            KeyCode::ShiftLeft,
+            KeyCode::ArrowRight,
+            Key::ArrowRight,
+        );
+        assert_eq!(state(&app, entity), ("hello".to_string(), 2, 0));
+
+        type_text(&mut app, "J");
+        assert_eq!(state(&app, entity), ("Jllo".to_string(), 1, 1));
+
+        press_with(&mut app, KeyCode::ShiftLeft, KeyCode::End, Key::End);
+        press(&mut app, KeyCode::Backspace, Key::Backspace);
+        assert_eq!(state(&app, entity), ("J".to_string(), 1, 1));
+    }
+
+    #[test]
+    fn control_moves_and_deletes_by_word() {
+        let input = OutlinedTextInput {
+            caret: 17,
+            anchor: 17,
+            ..default()
+        };
+        let (mut app, entity) = setup("hello brave world", input);
+
+        press_with(
+            &mut app,
+            KeyCode::ControlLeft,
+            KeyCode::ArrowLeft,
+            Key::ArrowLeft,
+        );
+        assert_eq!(state(&app, entity).1, 12);
+
+        press_with(
+            &mut app,
+            KeyCode::ControlLeft,
+            KeyCode::ArrowLeft,
+            Key::ArrowLeft,
+        );
+        assert_eq!(state(&app, entity).1, 6);
+
+        press_with(
+            &mut app,
+            KeyCode::ControlLeft,
+            KeyCode::ArrowRight,
+            Key::ArrowRight,
+        );
+        assert_eq!(state(&app, entity).1, 11);
+
+        press_with(
+            &mut app,
+            KeyCode::ControlLeft,
+            KeyCode::Backspace,
+            Key::Backspace,
+        );
+        assert_eq!(state(&app, entity), ("hello  world".to_string(), 6, 6));
+    }
+
+    #[test]
+    fn enter_inserts_a_newline_only_when_multiline() {
+        let (mut app, entity) = setup("ab", default());
+        press(&mut app, KeyCode::Enter, Key::Enter);
+        assert_eq!(state(&app, entity).0, "ab");
+
+        let input = OutlinedTextInput {
+            multiline: true,
+            caret: 1,
+            anchor: 1,
+            ..default()
+        };
+        let (mut app, entity) = setup("ab", input);
+        press(&mut app, KeyCode::Enter, Key::Enter);
+        assert_eq!(state(&app, entity), ("a\nb".to_string(), 2, 2));
+
+        press(&mut app, KeyCode::End, Key::End);
+        assert_eq!(state(&app, entity).1, 3);
+        press(&mut app, KeyCode::Home, Key::Home);
+        assert_eq!(state(&app, entity).1, 2);
+    }
+
+    #[test]
+    fn home_end_and_arrows_follow_wrapped_lines() {
+        let value = "hello brave world";
+        let input = OutlinedTextInput {
+            caret: 8,
+            anchor: 8,
+            ..default()
+        };
+        let (mut app, entity) = setup(value, input);
+        app.world_mut()
+            .entity_mut(entity)
+            .insert(monospace_layout(value, &[6, 12]));
+
+        press(&mut app, KeyCode::Home, Key::Home);
+        assert_eq!(state(&app, entity).1, 6);
+
+        press(&mut app, KeyCode::End, Key::End);
+        assert_eq!(state(&app, entity).1, 11);
+
+        press(&mut app, KeyCode::ArrowUp, Key::ArrowUp);
+        assert_eq!(state(&app, entity).1, 5);
+
+        press(&mut app, KeyCode::ArrowDown, Key::ArrowDown);
+        press(&mut app, KeyCode::ArrowDown, Key::ArrowDown);
+        assert_eq!(state(&app, entity).1, 17);
+
+        press(&mut app, KeyCode::ArrowDown, Key::ArrowDown);
+        assert_eq!(state(&app, entity).1, 17);
+
+        press(&mut app, KeyCode::ArrowUp, Key::ArrowUp);
+        press(&mut app, KeyCode::ArrowUp, Key::ArrowUp);
+        press(&mut app, KeyCode::ArrowUp, Key::ArrowUp);
+        assert_eq!(state(&app, entity).1, 0);
+    }
+
+    #[test]
+    fn grapheme_boundaries_keep_clusters_together() {
+        assert_eq!(grapheme_boundaries(""), [0]);
+        assert_eq!(grapheme_boundaries("ab"), [0, 1, 2]);
+        assert_eq!(grapheme_boundaries("e\u{301}x"), [0, 3, 4]);
+        assert_eq!(grapheme_boundaries("a👍🏽b"), [0, 1, 9, 10]);
+        assert_eq!(grapheme_boundaries("a\r\nb"), [0, 1, 3, 4]);
+    }
+
+    #[test]
+    fn word_boundaries_include_both_ends() {
+        assert_eq!(word_boundaries(""), [0]);
+        assert_eq!(word_boundaries("hello world"), [0, 5, 6, 11]);
+    }
+
+    #[test]
+    fn word_movement_skips_whitespace() {
+        let value = "hello  big world";
+
+        assert_eq!(next_boundary(value, 0, &Movement::Word), 5);
+        assert_eq!(next_boundary(value, 5, &Movement::Word), 10);
+        assert_eq!(next_boundary(value, 10, &Movement::Word), 16);
+        assert_eq!(next_boundary(value, 16, &Movement::Word), 16);
+
+        assert_eq!(previous_boundary(value, 16, &Movement::Word), 11);
+        assert_eq!(previous_boundary(value, 11, &Movement::Word), 7);
+        assert_eq!(previous_boundary(value, 7, &Movement::Word), 0);
+        assert_eq!(previous_boundary(value, 0, &Movement::Word), 0);
+    }
+
+    #[test]
+    fn grapheme_movement_steps_over_combining_marks() {
+        let value = "e\u{301}x";
+
+        assert_eq!(next_boundary(value, 0, &Movement::Grapheme), 3);
+        assert_eq!(previous_boundary(value, 4, &Movement::Grapheme), 3);
+        assert_eq!(previous_boundary(value, 3, &Movement::Grapheme), 0);
+        assert_eq!(next_boundary(value, 4, &Movement::Grapheme), 4);
+    }
+}
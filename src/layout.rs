@@ -0,0 +1,323 @@
+use bevy::prelude::*;
+use std::ops::Range;
+
+#[derive(Component, Clone, Debug, Default)]
+pub struct OutlinedTextLayout {
+    pub size: Vec2,
+    pub rect: Rect,
+    pub lines: Vec<OutlinedTextLayoutLine>,
+    pub glyphs: Vec<OutlinedTextLayoutGlyph>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct OutlinedTextLayoutLine {
+    pub baseline: f32,
+    pub left: f32,
+    pub width: f32,
+    pub ascent: f32,
+    pub descent: f32,
+    pub glyphs: Range<usize>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct OutlinedTextLayoutGlyph {
+    pub position: Vec2,
+    pub advance: f32,
+    pub line: usize,
+    pub section: usize,
+    pub byte_range: Range<usize>,
+    pub rtl: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutlinedTextHit {
+    pub section: usize,
+    pub byte_offset: usize,
+    pub trailing: bool,
+    pub cluster: Range<usize>,
+}
+
+impl OutlinedTextHit {
+    pub fn caret_offset(&self) -> usize {
+        if self.trailing {
+            self.cluster.end
+        } else {
+            self.byte_offset
+        }
+    }
+}
+
+struct LayoutCluster<'a> {
+    left: f32,
+    right: f32,
+    glyph: &'a OutlinedTextLayoutGlyph,
+}
+
+impl OutlinedTextLayout {
+    pub fn hit_test(&self, point: Vec2) -> Option<OutlinedTextHit> {
+        let line = self
+            .lines
+            .iter()
+            .find(|line| point.y >= line.baseline - line.descent)
+            .or(self.lines.last())?;
+
+        let mut clusters = self.clusters(line).peekable();
+        let cluster = loop {
+            let cluster = clusters.next()?;
+            if point.x < cluster.right || clusters.peek().is_none() {
+                break cluster;
+            }
+        };
+
+        let trailing = (point.x > (cluster.left + cluster.right) / 2.0) != cluster.glyph.rtl;
+
+        Some(OutlinedTextHit {
+            section: cluster.glyph.section,
+            byte_offset: cluster.glyph.byte_range.start,
+            trailing,
+            cluster: cluster.glyph.byte_range.clone(),
+        })
+    }
+
+    pub fn caret_rect(&self, section: usize, byte_offset: usize) -> Option<Rect> {
+        let in_section = |cluster: &LayoutCluster| cluster.glyph.section == section;
+
+        let leading = self.lines.iter().find_map(|line| {
+            self.clusters(line)
+                .filter(in_section)
+                .find(|cluster| cluster.glyph.byte_range.contains(&byte_offset))
+                .map(|cluster| {
+                    let x = if cluster.glyph.rtl {
+                        cluster.right
+                    } else {
+                        cluster.left
+                    };
+                    caret(line, x)
+                })
+        });
+
+        leading.or_else(|| {
+            self.lines.iter().find_map(|line| {
+                self.clusters(line)
+                    .filter(in_section)
+                    .find(|cluster| cluster.glyph.byte_range.end == byte_offset)
+                    .map(|cluster| {
+                        let x = if cluster.glyph.rtl {
+                            cluster.left
+                        } else {
+                            cluster.right
+                        };
+                        caret(line, x)
+                    })
+            })
+        })
+    }
+
+    pub fn selection_rects(&self, section: usize, range: Range<usize>) -> Vec<Rect> {
+        self.lines
+            .iter()
+            .filter_map(|line| {
+                let (left, right) = self
+                    .clusters(line)
+                    .filter(|cluster| {
+                        let byte_range = &cluster.glyph.byte_range;
+                        cluster.glyph.section == section
+                            && !byte_range.is_empty()
+                            && byte_range.start >= range.start
+                            && byte_range.end <= range.end
+                    })
+                    .fold(
+                        (f32::INFINITY, f32::NEG_INFINITY),
+                        |(left, right), cluster| (left.min(cluster.left), right.max(cluster.right)),
+                    );
+
+                (left < right).then(|| {
+                    Rect::new(
+                        left,
+                        line.baseline - line.descent,
+                        right,
+                        line.baseline + line.ascent,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    fn clusters<'a>(
+        &'a self,
+        line: &OutlinedTextLayoutLine,
+    ) -> impl Iterator<Item = LayoutCluster<'a>> + 'a {
+        self.glyphs[line.glyphs.clone()]
+            .chunk_by(|a, b| a.section == b.section && a.byte_range == b.byte_range)
+            .map(|glyphs| LayoutCluster {
+                left: glyphs
+                    .iter()
+                    .map(|glyph| glyph.position.x)
+                    .fold(f32::INFINITY, f32::min),
+                right: glyphs
+                    .iter()
+                    .map(|glyph| glyph.position.x + glyph.advance)
+                    .fold(f32::NEG_INFINITY, f32::max),
+                glyph: &glyphs[0],
+            })
+    }
+}
+
+fn caret(line: &OutlinedTextLayoutLine, x: f32) -> Rect {
+    Rect::new(
+        x,
+        line.baseline - line.descent,
+        x,
+        line.baseline + line.ascent,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(baseline: f32, glyphs: Range<usize>) -> OutlinedTextLayoutLine {
+        OutlinedTextLayoutLine {
+            baseline,
+            left: 0.0,
+            width: 30.0,
+            ascent: 10.0,
+            descent: 3.0,
+            glyphs,
+        }
+    }
+
+    fn glyph(
+        x: f32,
+        line: usize,
+        section: usize,
+        start: usize,
+        rtl: bool,
+    ) -> OutlinedTextLayoutGlyph {
+        OutlinedTextLayoutGlyph {
+            position: Vec2::new(x, 0.0),
+            advance: 10.0,
+            line,
+            section,
+            byte_range: start..start + 1,
+            rtl,
+        }
+    }
+
+    /// "abc" on a first line and "de" on a second line, ten units per glyph.
+    fn two_lines() -> OutlinedTextLayout {
+        OutlinedTextLayout {
+            lines: vec![line(0.0, 0..3), line(-20.0, 3..5)],
+            glyphs: vec![
+                glyph(0.0, 0, 0, 0, false),
+                glyph(10.0, 0, 0, 1, false),
+                glyph(20.0, 0, 0, 2, false),
+                glyph(0.0, 1, 0, 3, false),
+                glyph(10.0, 1, 0, 4, false),
+            ],
+            ..default()
+        }
+    }
+
+    #[test]
+    fn hit_test_finds_the_nearest_caret_offset() {
+        let layout = two_lines();
+        let caret = |x, y| layout.hit_test(Vec2::new(x, y)).unwrap().caret_offset();
+
+        assert_eq!(caret(4.0, 2.0), 0);
+        assert_eq!(caret(6.0, 2.0), 1);
+        assert_eq!(caret(24.0, 2.0), 2);
+        assert_eq!(caret(-5.0, 2.0), 0);
+        assert_eq!(caret(100.0, 2.0), 3);
+        assert_eq!(caret(6.0, -20.0), 4);
+        assert_eq!(caret(100.0, -100.0), 5);
+
+        let hit = layout.hit_test(Vec2::new(16.0, 0.0)).unwrap();
+        assert_eq!(
+            hit,
+            OutlinedTextHit {
+                section: 0,
+                byte_offset: 1,
+                trailing: true,
+                cluster: 1..2,
+            }
+        );
+    }
+
+    #[test]
+    fn hit_test_flips_sides_in_right_to_left_runs() {
+        let layout = OutlinedTextLayout {
+            lines: vec![line(0.0, 0..3)],
+            glyphs: vec![
+                glyph(0.0, 0, 0, 2, true),
+                glyph(10.0, 0, 0, 1, true),
+                glyph(20.0, 0, 0, 0, true),
+            ],
+            ..default()
+        };
+        let caret = |x| layout.hit_test(Vec2::new(x, 0.0)).unwrap().caret_offset();
+
+        assert_eq!(caret(28.0), 0);
+        assert_eq!(caret(22.0), 1);
+        assert_eq!(caret(2.0), 3);
+
+        assert_eq!(layout.caret_rect(0, 0).unwrap().min.x, 30.0);
+        assert_eq!(layout.caret_rect(0, 3).unwrap().min.x, 0.0);
+    }
+
+    #[test]
+    fn caret_rect_spans_the_line_height() {
+        let layout = two_lines();
+
+        assert_eq!(
+            layout.caret_rect(0, 1),
+            Some(Rect::new(10.0, -3.0, 10.0, 10.0))
+        );
+        // The start of the second line wins over the end of the first.
+        assert_eq!(
+            layout.caret_rect(0, 3),
+            Some(Rect::new(0.0, -23.0, 0.0, -10.0))
+        );
+        assert_eq!(
+            layout.caret_rect(0, 5),
+            Some(Rect::new(20.0, -23.0, 20.0, -10.0))
+        );
+        assert_eq!(layout.caret_rect(1, 0), None);
+        assert_eq!(OutlinedTextLayout::default().caret_rect(0, 0), None);
+    }
+
+    #[test]
+    fn selection_rects_cover_each_line() {
+        let layout = two_lines();
+
+        assert_eq!(
+            layout.selection_rects(0, 1..4),
+            [
+                Rect::new(10.0, -3.0, 30.0, 10.0),
+                Rect::new(0.0, -23.0, 10.0, -10.0),
+            ]
+        );
+        assert_eq!(
+            layout.selection_rects(0, 0..2),
+            [Rect::new(0.0, -3.0, 20.0, 10.0)]
+        );
+        assert!(layout.selection_rects(0, 1..1).is_empty());
+        assert!(layout.selection_rects(1, 0..5).is_empty());
+    }
+
+    #[test]
+    fn sections_are_kept_apart() {
+        let layout = OutlinedTextLayout {
+            lines: vec![line(0.0, 0..2)],
+            glyphs: vec![glyph(0.0, 0, 0, 0, false), glyph(10.0, 0, 1, 0, false)],
+            ..default()
+        };
+
+        assert_eq!(layout.caret_rect(1, 0).unwrap().min.x, 10.0);
+        assert_eq!(
+            layout.selection_rects(1, 0..1),
+            [Rect::new(10.0, -3.0, 20.0, 10.0)]
+        );
+        assert_eq!(layout.hit_test(Vec2::new(12.0, 0.0)).unwrap().section, 1);
+    }
+}
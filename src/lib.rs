@@ -1,26 +1,81 @@
+mod atlas;
+mod bidi;
+mod coverage;
+mod decoration;
+mod fill;
+mod input;
+mod layout;
+mod sdf;
+
+pub use atlas::{GlyphAtlas, GlyphAtlasEntry, GlyphAtlasKey, GlyphAtlasKind, GlyphAtlasStatistics};
+pub use decoration::{BackgroundStyle, TextDecoration};
+pub use fill::{OutlinedTextFillMaterial, TextFill, TextFillMapping};
+pub use input::{
+    blink_text_input_carets, edit_text_input, update_text_input_visuals, OutlinedTextCaret,
+    OutlinedTextInput, OutlinedTextInputVisual,
+};
+pub use layout::{
+    OutlinedTextHit, OutlinedTextLayout, OutlinedTextLayoutGlyph, OutlinedTextLayoutLine,
+};
+pub use sdf::OutlinedTextSdfMaterial;
+pub use swash::{tag_from_bytes, Tag};
+
+use atlas::SUBPIXEL_STEPS;
 use bevy::asset::io::Reader;
 use bevy::asset::LoadContext;
-use bevy::asset::{AssetLoader, AsyncReadExt};
+use bevy::asset::{load_internal_asset, AssetLoader, AsyncReadExt};
 use bevy::prelude::LinearRgba;
 use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
 use bevy::render::render_asset::RenderAssetUsages;
-use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::view::VisibilitySystems;
 use bevy::render::{Extract, RenderApp};
-use bevy::sprite::{Anchor, ExtractedSprite, ExtractedSprites, SpriteSource, SpriteSystem};
+use bevy::sprite::{
+    Anchor, ExtractedSprite, ExtractedSprites, Material2dPlugin, MaterialMesh2dBundle,
+    SpriteSource, SpriteSystem,
+};
+use bevy::transform::TransformSystem;
 use bevy::utils::HashMap;
 use bevy::window::{PrimaryWindow, WindowScaleFactorChanged};
-use std::mem;
+use fill::{OUTLINED_TEXT_FILL_SHADER_HANDLE, TEXT_SHADER_HANDLE};
+use sdf::{OUTLINED_TEXT_SDF_SHADER_HANDLE, SDF_SIZE, SDF_SPREAD};
+use serde::{Deserialize, Serialize};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::Range;
 use std::sync::Arc;
-use swash::scale::{Render, ScaleContext, Scaler, Source};
-use swash::shape::{ShapeContext, Shaper};
-use swash::text::cluster::{CharCluster, Parser, Token, Whitespace};
-use swash::text::{Codepoint, Script};
-use swash::zeno::{Cap, Format, Join, Stroke};
-use swash::{CacheKey, Charmap, FontRef, GlyphId};
+use swash::scale::{Render, ScaleContext, Scaler, Source, StrikeWith};
+use swash::shape::cluster::Glyph;
+use swash::shape::{Direction, ShapeContext, Shaper};
+use swash::text::cluster::{Boundary, CharCluster, CharInfo, Parser, Status, Token, Whitespace};
+use swash::text::{analyze, BidiClass, Codepoint, Script};
+use swash::zeno::{Cap, Format, Join, Stroke, Vector};
+use swash::{CacheKey, FontDataRef, FontRef, GlyphId, Metrics, NormalizedCoord, StringId};
 use thiserror::Error;
 
 type SwashImage = swash::scale::image::Image;
 
+const LAYER_DEPTH: f32 = 0.001;
+const AUTO_SIZE_STEPS: usize = 8;
+/// Normalized variation coordinates are snapped to this many steps per unit, so
+/// an animated axis reuses at most `2 * VARIATION_STEPS + 1` atlas entries per
+/// glyph instead of creating new ones for every frame.
+const VARIATION_STEPS: i32 = 64;
+/// Width of the steps stretched decorations are split into when they fade out.
+const FADE_STEP: f32 = 2.0;
+const SECTION_BACKGROUND_LAYER: usize = 64;
+const TEXT_BACKGROUND_LAYER: usize = SECTION_BACKGROUND_LAYER + 1;
+
+fn layer_z(layer: usize) -> f32 {
+    -(layer as f32) * LAYER_DEPTH
+}
+
+fn outline_layer_z(outline: &OutlineLayer, index: usize, count: usize) -> f32 {
+    match outline.placement {
+        OutlinePlacement::Inside => -layer_z(count - index),
+        OutlinePlacement::Outside | OutlinePlacement::Center => layer_z(index + 1),
+    }
+}
+
 #[derive(Asset, TypePath, Debug, Clone)]
 pub struct OutlinedFont {
     data: Arc<Vec<u8>>,
@@ -29,7 +84,7 @@ pub struct OutlinedFont {
 }
 
 impl OutlinedFont {
-    fn as_ref(&self) -> FontRef {
+    fn as_ref(&self) -> FontRef<'_> {
         FontRef {
             data: &self.data,
             offset: self.offset,
@@ -45,6 +100,69 @@ pub enum OutlineFontLoaderError {
     Io(#[from] std::io::Error),
     #[error("invalid font")]
     InvalidFont,
+    #[error("font face {0:?} not found")]
+    FaceNotFound(OutlinedFontFace),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum OutlinedFontFace {
+    Index(usize),
+    Name(String),
+}
+
+impl Default for OutlinedFontFace {
+    fn default() -> Self {
+        Self::Index(0)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct OutlinedFontLoaderSettings {
+    pub face: OutlinedFontFace,
+}
+
+fn face_matches_name(font_ref: &FontRef, name: &str) -> bool {
+    let strings = font_ref.localized_strings();
+
+    [StringId::PostScript, StringId::Family, StringId::Full]
+        .into_iter()
+        .filter_map(|id| strings.find_by_id(id, None))
+        .any(|string| string.chars().eq(name.chars()))
+}
+
+/// Selects `face` from the font file in `data`, passing every face of a
+/// collection to `add_labeled` as `face{index}`.
+fn load_faces(
+    data: Arc<Vec<u8>>,
+    face: &OutlinedFontFace,
+    mut add_labeled: impl FnMut(String, OutlinedFont),
+) -> Result<OutlinedFont, OutlineFontLoaderError> {
+    let font_data = FontDataRef::new(&data).ok_or(OutlineFontLoaderError::InvalidFont)?;
+
+    let mut selected = None;
+
+    for (index, font_ref) in font_data.fonts().enumerate() {
+        let font = OutlinedFont {
+            data: data.clone(),
+            offset: font_ref.offset,
+            key: font_ref.key,
+        };
+
+        let is_selected = match face {
+            OutlinedFontFace::Index(face_index) => *face_index == index,
+            OutlinedFontFace::Name(name) => face_matches_name(&font_ref, name),
+        };
+
+        if is_selected && selected.is_none() {
+            selected = Some(font.clone());
+        }
+
+        if font_data.is_collection() {
+            add_labeled(format!("face{index}"), font);
+        }
+    }
+
+    selected.ok_or_else(|| OutlineFontLoaderError::FaceNotFound(face.clone()))
 }
 
 #[derive(Default)]
@@ -52,34 +170,24 @@ pub struct OutlinedFontLoader;
 
 impl AssetLoader for OutlinedFontLoader {
     type Asset = OutlinedFont;
-    type Settings = ();
+    type Settings = OutlinedFontLoaderSettings;
     type Error = OutlineFontLoaderError;
     async fn load<'a>(
         &'a self,
         reader: &'a mut Reader<'_>,
-        _settings: &'a (),
-        _load_context: &'a mut LoadContext<'_>,
+        settings: &'a OutlinedFontLoaderSettings,
+        load_context: &'a mut LoadContext<'_>,
     ) -> Result<OutlinedFont, OutlineFontLoaderError> {
         let mut bytes = Vec::new();
         reader.read_to_end(&mut bytes).await?;
 
-        let font = FontRef::from_index(&bytes, 0);
-
-        if let Some(font_ref) = font {
-            let (offset, key) = (font_ref.offset, font_ref.key);
-
-            Ok(OutlinedFont {
-                data: Arc::new(bytes),
-                offset,
-                key,
-            })
-        } else {
-            Err(OutlineFontLoaderError::InvalidFont)
-        }
+        load_faces(Arc::new(bytes), &settings.face, |label, font| {
+            load_context.add_labeled_asset(label, font);
+        })
     }
 
     fn extensions(&self) -> &[&str] {
-        &["ttf", "otf"]
+        &["ttf", "otf", "ttc", "otc"]
     }
 }
 
@@ -88,29 +196,293 @@ pub struct OutlinedText {
     pub sections: Vec<OutlinedTextSection>,
     pub font_style: OutlinedFontStyle,
     pub justify: JustifyOutlinedText,
+    pub direction: OutlinedTextDirection,
+    pub render_mode: OutlinedTextRenderMode,
+    pub background: Option<BackgroundStyle>,
+    pub justify_width: Option<f32>,
+    pub max_lines: Option<usize>,
+    pub overflow: OverflowMode,
+}
+
+impl OutlinedText {
+    fn font_handles(&self) -> impl Iterator<Item = &Handle<OutlinedFont>> {
+        std::iter::once(&self.font_style.font)
+            .chain(&self.font_style.fallbacks)
+            .chain(
+                self.sections
+                    .iter()
+                    .filter_map(|section| section.font.as_ref()),
+            )
+    }
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct OutlinedTextSection {
     pub value: String,
-    pub color: Color,
+    pub color: TextFill,
     pub outline: OutlineStyle,
+    pub shadow: Option<ShadowStyle>,
+    pub tint_color_glyphs: bool,
+    pub underline: Option<TextDecoration>,
+    pub strikethrough: Option<TextDecoration>,
+    pub background: Option<BackgroundStyle>,
+    pub features: Vec<(Tag, u16)>,
+    pub font: Option<Handle<OutlinedFont>>,
+    pub size: Option<f32>,
+    pub variations: Vec<(Tag, f32)>,
+}
+
+impl OutlinedTextSection {
+    fn shapes_like(&self, other: &OutlinedTextSection) -> bool {
+        self.features == other.features
+            && self.font == other.font
+            && self.size == other.size
+            && self.variations == other.variations
+    }
 }
 
 #[derive(Component, Clone, Debug, Default)]
 pub struct OutlinedFontStyle {
     pub font: Handle<OutlinedFont>,
+    pub fallbacks: Vec<Handle<OutlinedFont>>,
+    pub size: f32,
+    pub variations: Vec<(Tag, f32)>,
+    pub weight: Option<f32>,
+    pub width: Option<f32>,
+    pub features: Vec<(Tag, u16)>,
+    pub letter_spacing: f32,
+    pub word_spacing: f32,
+    pub line_height: LineHeight,
+    pub paragraph_spacing: f32,
+    pub auto_size: Option<AutoSize>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AutoSize {
+    pub min_size: f32,
+    pub max_size: f32,
+}
+
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct OutlinedTextFittedSize {
     pub size: f32,
 }
 
+impl OutlinedFontStyle {
+    fn variation_settings(&self, section: &OutlinedTextSection) -> Vec<(Tag, f32)> {
+        let mut settings = self.variations.clone();
+        settings.extend(self.weight.map(|weight| (tag_from_bytes(b"wght"), weight)));
+        settings.extend(self.width.map(|width| (tag_from_bytes(b"wdth"), width)));
+        settings.extend_from_slice(&section.variations);
+        settings
+    }
+
+    fn feature_settings(&self, section: &OutlinedTextSection) -> Vec<(Tag, u16)> {
+        let mut settings: Vec<(Tag, u16)> = self
+            .features
+            .iter()
+            .filter(|(tag, _)| !section.features.iter().any(|(other, _)| other == tag))
+            .copied()
+            .collect();
+        settings.extend_from_slice(&section.features);
+        settings
+    }
+}
+
+struct FontInstance<'a> {
+    font_ref: FontRef<'a>,
+    coords: Vec<NormalizedCoord>,
+    variations_key: u64,
+    size: f32,
+    metrics: Metrics,
+    has_color: bool,
+}
+
+impl<'a> FontInstance<'a> {
+    fn new(font_ref: FontRef<'a>, variations: &[(Tag, f32)], size: f32) -> Self {
+        let coords: Vec<NormalizedCoord> = font_ref
+            .variations()
+            .normalized_coords(variations)
+            .map(quantize_coord)
+            .collect();
+
+        let mut hasher = DefaultHasher::new();
+        coords.hash(&mut hasher);
+
+        Self {
+            font_ref,
+            metrics: font_ref.metrics(&coords).scale(size),
+            coords,
+            variations_key: hasher.finish(),
+            size,
+            has_color: [b"COLR", b"CBDT", b"sbix"]
+                .into_iter()
+                .any(|tag| font_ref.table(tag_from_bytes(tag)).is_some()),
+        }
+    }
+}
+
+fn quantize_coord(coord: NormalizedCoord) -> NormalizedCoord {
+    let step = (1 << 14) / VARIATION_STEPS;
+    ((coord as f32 / step as f32).round() as i32 * step) as NormalizedCoord
+}
+
 #[derive(Debug, Clone, Default)]
 pub enum OutlineStyle {
     #[default]
     None,
     Outline {
         width: f32,
-        color: Color,
+        color: TextFill,
+        join: OutlineJoin,
+        cap: OutlineCap,
+        miter_limit: f32,
+        placement: OutlinePlacement,
     },
+    Stacked(Vec<OutlineLayer>),
+}
+
+impl OutlineStyle {
+    /// A single centered outline with the default join, cap and miter limit.
+    pub fn outline(width: f32, color: impl Into<TextFill>) -> Self {
+        OutlineLayer::new(width, color).into()
+    }
+
+    fn layers(&self) -> Vec<OutlineLayer> {
+        match self {
+            OutlineStyle::None => Vec::new(),
+            OutlineStyle::Outline {
+                width,
+                color,
+                join,
+                cap,
+                miter_limit,
+                placement,
+            } => vec![OutlineLayer {
+                width: *width,
+                color: color.clone(),
+                join: *join,
+                cap: *cap,
+                miter_limit: *miter_limit,
+                placement: *placement,
+            }],
+            OutlineStyle::Stacked(layers) => layers.clone(),
+        }
+    }
+}
+
+impl From<OutlineLayer> for OutlineStyle {
+    fn from(layer: OutlineLayer) -> Self {
+        OutlineStyle::Outline {
+            width: layer.width,
+            color: layer.color,
+            join: layer.join,
+            cap: layer.cap,
+            miter_limit: layer.miter_limit,
+            placement: layer.placement,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OutlineLayer {
+    pub width: f32,
+    pub color: TextFill,
+    pub join: OutlineJoin,
+    pub cap: OutlineCap,
+    pub miter_limit: f32,
+    pub placement: OutlinePlacement,
+}
+
+impl OutlineLayer {
+    pub fn new(width: f32, color: impl Into<TextFill>) -> Self {
+        Self {
+            width,
+            color: color.into(),
+            ..default()
+        }
+    }
+
+    fn outer_width(&self) -> f32 {
+        match self.placement {
+            OutlinePlacement::Center => self.width / 2.0,
+            OutlinePlacement::Outside => self.width,
+            OutlinePlacement::Inside => 0.0,
+        }
+    }
+
+    fn stroke_style(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.join.hash(&mut hasher);
+        self.cap.hash(&mut hasher);
+        self.miter_limit.to_bits().hash(&mut hasher);
+        self.placement.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl Default for OutlineLayer {
+    fn default() -> Self {
+        Self {
+            width: 0.0,
+            color: TextFill::default(),
+            join: OutlineJoin::default(),
+            cap: OutlineCap::default(),
+            miter_limit: 4.0,
+            placement: OutlinePlacement::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum OutlineJoin {
+    Miter,
+    #[default]
+    Round,
+    Bevel,
+}
+
+impl From<OutlineJoin> for Join {
+    fn from(join: OutlineJoin) -> Self {
+        match join {
+            OutlineJoin::Miter => Join::Miter,
+            OutlineJoin::Round => Join::Round,
+            OutlineJoin::Bevel => Join::Bevel,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum OutlineCap {
+    Butt,
+    #[default]
+    Square,
+    Round,
+}
+
+impl From<OutlineCap> for Cap {
+    fn from(cap: OutlineCap) -> Self {
+        match cap {
+            OutlineCap::Butt => Cap::Butt,
+            OutlineCap::Square => Cap::Square,
+            OutlineCap::Round => Cap::Round,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum OutlinePlacement {
+    Outside,
+    #[default]
+    Center,
+    Inside,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ShadowStyle {
+    pub offset: Vec2,
+    pub blur_radius: f32,
+    pub color: Color,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -119,12 +491,83 @@ pub enum JustifyOutlinedText {
     Left,
     Center,
     Right,
+    Start,
+    End,
+    Justified {
+        inter_character: bool,
+    },
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum OverflowMode {
+    #[default]
+    Visible,
+    Clip,
+    Ellipsis(String),
+    FadeOut {
+        length: f32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LineHeight {
+    #[default]
+    Normal,
+    Absolute(f32),
+    Multiplier(f32),
+}
+
+impl LineHeight {
+    fn resolve(self, size: f32, scale_factor: f32) -> Option<f32> {
+        match self {
+            LineHeight::Normal => None,
+            LineHeight::Absolute(height) => Some(height / scale_factor),
+            LineHeight::Multiplier(multiplier) => Some(size * multiplier),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutlinedTextRenderMode {
+    #[default]
+    Bitmap,
+    Sdf {
+        softness: f32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutlinedTextDirection {
+    #[default]
+    Auto,
+    LeftToRight,
+    RightToLeft,
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct OutlinedTextBounds {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl OutlinedTextBounds {
+    pub const UNBOUNDED: Self = Self {
+        width: f32::MAX,
+        height: f32::MAX,
+    };
+}
+
+impl Default for OutlinedTextBounds {
+    fn default() -> Self {
+        Self::UNBOUNDED
+    }
 }
 
 #[derive(Bundle, Clone, Debug, Default)]
 pub struct OutlinedText2dBundle {
     pub text: OutlinedText,
     pub text_anchor: Anchor,
+    pub text_bounds: OutlinedTextBounds,
     pub transform: Transform,
     pub global_transform: GlobalTransform,
     pub visibility: Visibility,
@@ -133,91 +576,240 @@ pub struct OutlinedText2dBundle {
     pub sprite_source: SpriteSource,
 }
 
-fn glyph_to_bitmap(glyph_id: GlyphId, scaler: &mut Scaler) -> SwashImage {
+fn build_scaler<'a>(
+    scale_context: &'a mut ScaleContext,
+    font: &'a FontInstance,
+    size: f32,
+    hint: bool,
+) -> Scaler<'a> {
+    scale_context
+        .builder(font.font_ref)
+        .normalized_coords(&font.coords)
+        .size(size)
+        .hint(hint)
+        .build()
+}
+
+fn glyph_to_bitmap(glyph_id: GlyphId, offset: Vector, scaler: &mut Scaler) -> SwashImage {
     Render::new(&[Source::Outline])
         .format(Format::Alpha)
+        .offset(offset)
         .render(scaler, glyph_id)
-        .unwrap()
+        .unwrap_or_else(SwashImage::new)
+}
+
+fn glyph_to_color_bitmap(glyph_id: GlyphId, offset: Vector, scaler: &mut Scaler) -> SwashImage {
+    Render::new(&[
+        Source::ColorOutline(0),
+        Source::ColorBitmap(StrikeWith::BestFit),
+        Source::Outline,
+    ])
+    .format(Format::Alpha)
+    .offset(offset)
+    .render(scaler, glyph_id)
+    .unwrap_or_else(SwashImage::new)
+}
+
+fn glyph_silhouette(
+    glyph_id: GlyphId,
+    color: bool,
+    offset: Vector,
+    scaler: &mut Scaler,
+) -> SwashImage {
+    if color {
+        coverage::alpha_channel(&glyph_to_color_bitmap(glyph_id, offset, scaler))
+    } else {
+        glyph_to_bitmap(glyph_id, offset, scaler)
+    }
+}
+
+fn outline_to_bitmap(
+    glyph_id: GlyphId,
+    outline: &OutlineLayer,
+    width: f32,
+    color: bool,
+    offset: Vector,
+    scaler: &mut Scaler,
+) -> SwashImage {
+    if !color {
+        return glyph_outline_to_bitmap(glyph_id, outline, width, offset, scaler);
+    }
+
+    let silhouette = glyph_silhouette(glyph_id, color, offset, scaler);
+    let (outer, inner) = match outline.placement {
+        OutlinePlacement::Center => (width / 2.0, -width / 2.0),
+        OutlinePlacement::Outside => (width, 0.0),
+        OutlinePlacement::Inside => (0.0, -width),
+    };
+
+    coverage::subtract(
+        &coverage::offset(&silhouette, outer),
+        &coverage::offset(&silhouette, inner),
+    )
 }
 
 fn glyph_outline_to_bitmap(
     glyph_id: GlyphId,
-    stroke_width: f32,
+    outline: &OutlineLayer,
+    width: f32,
+    offset: Vector,
     scaler: &mut Scaler,
 ) -> SwashImage {
-    Render::new(&[Source::Outline])
+    let stroke = Render::new(&[Source::Outline])
         .format(Format::Alpha)
-        .style(
-            Stroke::new(stroke_width)
-                .cap(Cap::Square)
-                .join(Join::Round)
-                .miter_limit(0.0),
-        )
+        .offset(offset)
+        .style(outline_stroke(outline, width))
         .render(scaler, glyph_id)
-        .unwrap()
+        .unwrap_or_else(SwashImage::new);
+
+    clip_outline(outline, stroke, || {
+        glyph_to_bitmap(glyph_id, offset, scaler)
+    })
 }
 
-fn bitmap_to_image(bitmap: &SwashImage, color: Color) -> Image {
-    let color: Srgba = color.into();
-    let red = (color.red * 255.0) as u8;
-    let green = (color.green * 255.0) as u8;
-    let blue = (color.blue * 255.0) as u8;
+fn outline_stroke(outline: &OutlineLayer, width: f32) -> Stroke<'static> {
+    let stroke_width = match outline.placement {
+        OutlinePlacement::Center => width,
+        OutlinePlacement::Outside | OutlinePlacement::Inside => width * 2.0,
+    };
+
+    let mut stroke = Stroke::new(stroke_width);
+    stroke
+        .cap(outline.cap.into())
+        .join(outline.join.into())
+        .miter_limit(outline.miter_limit);
+    stroke
+}
 
-    Image::new(
-        Extent3d {
-            width: bitmap.placement.width,
-            height: bitmap.placement.height,
-            depth_or_array_layers: 1,
-        },
-        TextureDimension::D2,
-        bitmap
-            .data
-            .iter()
-            .flat_map(|alpha| [red, green, blue, *alpha])
-            .collect::<Vec<u8>>(),
-        TextureFormat::Rgba8UnormSrgb,
-        RenderAssetUsages::default(),
-    )
+fn clip_outline(
+    outline: &OutlineLayer,
+    stroke: SwashImage,
+    fill: impl FnOnce() -> SwashImage,
+) -> SwashImage {
+    match outline.placement {
+        OutlinePlacement::Center => stroke,
+        OutlinePlacement::Outside => coverage::subtract(&stroke, &fill()),
+        OutlinePlacement::Inside => coverage::intersect(&stroke, &fill()),
+    }
 }
 
 #[derive(Resource, Default)]
 pub struct OutlinedTextImages {
-    cache: HashMap<Entity, Vec<OutlinedTextImage>>,
+    cache: HashMap<Entity, OutlinedTextGlyphs>,
+    mesh_entities: HashMap<Entity, Vec<Entity>>,
+    pending_meshes: Vec<Entity>,
+}
+
+#[derive(Default)]
+struct OutlinedTextGlyphs {
+    glyphs: Vec<GlyphImage>,
+    rect: Rect,
+    /// Font size each section was laid out at, after fitting.
+    section_sizes: Vec<f32>,
 }
 
+#[derive(Clone)]
 struct GlyphImage {
+    key: GlyphAtlasKey,
+    section: usize,
     offset_x: f32,
     offset_y: f32,
     offset_z: f32,
-    image: Image,
+    size: Vec2,
+    color: LinearRgba,
+    fill: Option<TextFill>,
+    image: Handle<Image>,
+    rect: Rect,
+}
+
+impl GlyphImage {
+    fn new(
+        key: GlyphAtlasKey,
+        section: usize,
+        origin: Vec3,
+        fill: &TextFill,
+        entry: GlyphAtlasEntry,
+        scale: f32,
+    ) -> Self {
+        let rect = entry.rect.as_rect();
+
+        Self {
+            key,
+            section,
+            offset_x: origin.x + entry.left as f32 * scale,
+            offset_y: origin.y + (entry.top as f32 - rect.height()) * scale,
+            offset_z: origin.z,
+            size: rect.size() * scale,
+            color: fill.solid_color().unwrap_or(LinearRgba::WHITE),
+            fill: fill.solid_color().is_none().then(|| fill.clone()),
+            image: entry.image,
+            rect,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ShapedCluster {
+    section: usize,
+    font: usize,
+    level: u8,
+    paragraph_level: u8,
+    bidi_class: BidiClass,
+    glyphs: Vec<Glyph>,
+    range: Range<usize>,
+    advance: f32,
+    letter_spacing: f32,
+    boundary: Boundary,
+    whitespace: Whitespace,
 }
 
 #[derive(Default)]
 struct OutlinedGlyphLine {
     glyphs: Vec<GlyphImage>,
     width: f32,
+    ascent: f32,
+    descent: f32,
+    leading: f32,
+    baseline: f32,
+    rtl: bool,
+    paragraph_end: bool,
+    layout_glyphs: Vec<OutlinedTextLayoutGlyph>,
 }
 
-struct OutlinedTextImage {
-    x: f32,
-    y: f32,
-    z: f32,
-    image: Handle<Image>,
-}
-
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn create_missing_text(
+    mut commands: Commands,
     fonts: Res<Assets<OutlinedFont>>,
-    text_query: Query<(Entity, Ref<OutlinedText>, Ref<Anchor>)>,
+    text_query: Query<(
+        Entity,
+        Ref<OutlinedText>,
+        Ref<Anchor>,
+        Option<Ref<OutlinedTextBounds>>,
+        Option<&OutlinedTextFittedSize>,
+    )>,
     mut removed: RemovedComponents<OutlinedText>,
     mut scale_factor_changed: EventReader<WindowScaleFactorChanged>,
+    mut font_events: EventReader<AssetEvent<OutlinedFont>>,
     mut images: ResMut<Assets<Image>>,
+    mut glyph_atlas: ResMut<GlyphAtlas>,
     mut outlined_text_images: ResMut<OutlinedTextImages>,
     windows: Query<&Window, With<PrimaryWindow>>,
 ) {
     let factor_changed = scale_factor_changed.read().last().is_some();
+    // Fallback fonts may arrive after the text was first laid out without them.
+    let loaded_fonts: Vec<AssetId<OutlinedFont>> = font_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
 
     for entity in removed.read() {
-        outlined_text_images.cache.remove(&entity);
+        if let Some(glyphs) = outlined_text_images.cache.remove(&entity) {
+            release_glyph_images(&mut glyph_atlas, &glyphs.glyphs);
+        }
+        outlined_text_images.pending_meshes.push(entity);
     }
 
     let scale_factor = windows
@@ -228,280 +820,1421 @@ pub fn create_missing_text(
     let mut shape_context = ShapeContext::new();
     let mut scale_context = ScaleContext::new();
 
-    for (entity, text, anchor) in text_query.iter() {
-        if !factor_changed
-            && !text.is_changed()
+    for (entity, text, anchor, bounds, fitted_size) in text_query.iter() {
+        let layout_changed = factor_changed
+            || text.is_changed()
+            || (!loaded_fonts.is_empty()
+                && text
+                    .font_handles()
+                    .any(|handle| loaded_fonts.contains(&handle.id())))
+            || bounds.as_ref().is_some_and(DetectChanges::is_changed);
+
+        if !layout_changed
             && !anchor.is_changed()
             && outlined_text_images.cache.contains_key(&entity)
         {
             continue;
         }
 
-        let handle = &text.font_style.font;
-
-        if let Some(outlined_font) = fonts.get(handle) {
-            let glyph_images = create_glyph_images(
-                &mut shape_context,
-                &mut scale_context,
-                text,
-                anchor,
-                outlined_font.as_ref(),
-                scale_factor,
-            );
-
-            let (glyphs, outlines): (Vec<_>, Vec<_>) = glyph_images
-                .into_iter()
-                .partition(|glyph| glyph.offset_z == 0.0);
-
-            let mut glyph_images = Vec::new();
-
-            if let Some(text_image) = compose_glyph_images(&mut images, &glyphs) {
-                glyph_images.push(text_image);
+        let font_size = match (text.font_style.auto_size, fitted_size) {
+            (Some(_), Some(fitted_size)) if !layout_changed => fitted_size.size,
+            (Some(auto_size), _) => {
+                let Some(size) = fit_font_size(
+                    &mut shape_context,
+                    &fonts,
+                    &text,
+                    bounds.as_deref(),
+                    auto_size,
+                    scale_factor,
+                ) else {
+                    continue;
+                };
+                commands
+                    .entity(entity)
+                    .insert(OutlinedTextFittedSize { size });
+                size
             }
+            (None, _) => text.font_style.size,
+        };
 
-            if let Some(outline_image) = compose_glyph_images(&mut images, &outlines) {
-                glyph_images.push(outline_image);
+        if let Some((glyphs, layout)) = create_glyph_images(
+            &mut shape_context,
+            &mut scale_context,
+            &mut glyph_atlas,
+            &mut images,
+            &fonts,
+            text,
+            anchor,
+            bounds.as_deref(),
+            font_size,
+            scale_factor,
+        ) {
+            commands.entity(entity).insert(layout);
+            if let Some(stale) = outlined_text_images.cache.insert(entity, glyphs) {
+                release_glyph_images(&mut glyph_atlas, &stale.glyphs);
             }
-
-            outlined_text_images.cache.insert(entity, glyph_images);
+            outlined_text_images.pending_meshes.push(entity);
         }
     }
+
+    glyph_atlas.write_pending(&mut images);
+    glyph_atlas.free_unused_pages(&mut images);
+}
+
+fn release_glyph_images(glyph_atlas: &mut GlyphAtlas, glyph_images: &[GlyphImage]) {
+    for glyph_image in glyph_images {
+        glyph_atlas.release(&glyph_image.key);
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn create_glyph_images(
     shape_context: &mut ShapeContext,
     scale_context: &mut ScaleContext,
+    glyph_atlas: &mut GlyphAtlas,
+    images: &mut Assets<Image>,
+    fonts: &Assets<OutlinedFont>,
     text: Ref<OutlinedText>,
     anchor: Ref<Anchor>,
-    font_ref: FontRef,
+    bounds: Option<&OutlinedTextBounds>,
+    font_size: f32,
     scale_factor: f32,
-) -> Vec<GlyphImage> {
+) -> Option<(OutlinedTextGlyphs, OutlinedTextLayout)> {
     let sections = &text.sections;
     if sections.is_empty() {
-        return Vec::new();
+        return Some(default());
     }
 
-    let mut lines: Vec<OutlinedGlyphLine> = Vec::new();
-    let mut current_line = OutlinedGlyphLine::default();
-
-    let size = text.font_style.size / scale_factor;
+    let font_style = &text.font_style;
+    let section_fonts = text_section_fonts(fonts, &text, font_size, scale_factor)?;
 
-    let script = Script::Latin;
-    let mut shaper = shape_context
-        .builder(font_ref)
-        .script(script)
-        .size(size)
-        .build();
+    let untinted = TextFill::Solid(Color::WHITE);
+    let mut clusters = shape_text(shape_context, &section_fonts, &text);
+    apply_spacing(
+        &mut clusters,
+        font_style.letter_spacing / scale_factor,
+        font_style.word_spacing / scale_factor,
+    );
+    let default_paragraph_level = match text.direction {
+        OutlinedTextDirection::RightToLeft => 1,
+        _ => 0,
+    };
 
-    let metrics = shaper.metrics();
-    let ascent = metrics.ascent;
-    let descent = metrics.descent;
-    let leading = metrics.leading;
-    let line_height = descent + ascent + leading;
+    let max_width = bounds.map_or(f32::MAX, |bounds| bounds.width);
+    let max_height = bounds.map_or(f32::MAX, |bounds| bounds.height);
+    let mut lines: Vec<OutlinedGlyphLine> = Vec::new();
+    let mut line_ranges = break_lines(&clusters, max_width);
+
+    let mut max_lines = text.max_lines;
+    if matches!(text.overflow, OverflowMode::Ellipsis(_)) && max_height < f32::MAX {
+        let fitting = fitting_lines(
+            &clusters,
+            &line_ranges,
+            &section_fonts,
+            font_style,
+            max_height,
+            scale_factor,
+        );
+        max_lines = Some(max_lines.map_or(fitting, |max_lines| max_lines.min(fitting)));
+    }
 
-    let mut x = 0.0;
-    let mut scaler = scale_context
-        .builder(font_ref)
-        .size(size)
-        .hint(true)
-        .build();
+    let truncated = max_lines.is_some_and(|max_lines| line_ranges.len() > max_lines);
+    if let Some(max_lines) = max_lines {
+        line_ranges.truncate(max_lines);
+    }
+    trim_letter_spacing(&mut clusters, &line_ranges);
+
+    let justify_width = match text.justify {
+        JustifyOutlinedText::Justified { .. } => Some(
+            text.justify_width
+                .or((max_width < f32::MAX).then_some(max_width))
+                .unwrap_or_else(|| {
+                    line_ranges
+                        .iter()
+                        .map(|range| {
+                            content_width(trim_trailing_whitespace(&clusters[range.clone()]))
+                        })
+                        .fold(0.0, f32::max)
+                }),
+        ),
+        _ => None,
+    };
+
+    for (line_index, line_range) in line_ranges.iter().enumerate() {
+        let paragraph_end = ends_paragraph(&clusters, line_range);
+        let line_clusters = &clusters[line_range.clone()];
+        let paragraph_level = line_clusters
+            .first()
+            .map_or(default_paragraph_level, |cluster| cluster.paragraph_level);
+
+        let mut content = trim_trailing_whitespace(line_clusters);
+        let overflowing = (truncated && line_index + 1 == line_ranges.len())
+            || content_width(content) > max_width;
+
+        let ellipsized;
+        if let (true, OverflowMode::Ellipsis(ellipsis)) = (overflowing, &text.overflow) {
+            ellipsized = ellipsize(
+                shape_context,
+                &section_fonts,
+                &text,
+                content,
+                ellipsis,
+                max_width,
+                paragraph_level,
+                scale_factor,
+            );
+            content = &ellipsized;
+        }
+        let levels = line_levels(content, paragraph_level);
 
-    for (index, section) in sections.iter().enumerate() {
-        add_section_to_shaper(
-            &mut shaper,
-            section,
-            script,
-            font_ref.charmap(),
-            index as u32,
+        let mut line = OutlinedGlyphLine {
+            rtl: bidi::is_rtl(paragraph_level),
+            paragraph_end,
+            ..default()
+        };
+        set_line_metrics(
+            &mut line,
+            line_clusters,
+            &section_fonts,
+            font_style.line_height,
+            scale_factor,
         );
-    }
 
-    shaper.shape_with(|glyph_cluster| {
-        let related_section = &sections[glyph_cluster.data as usize];
-        let color = related_section.color;
-        let outline = &related_section.outline;
+        let inter_character = matches!(
+            text.justify,
+            JustifyOutlinedText::Justified {
+                inter_character: true
+            }
+        );
+        let is_gap = |position: usize, cluster: &ShapedCluster| {
+            if inter_character {
+                position + 1 < content.len()
+            } else {
+                cluster.whitespace.is_space_or_nbsp()
+            }
+        };
+        let gap_spacing = justify_width
+            .filter(|_| !paragraph_end && line_index + 1 < line_ranges.len())
+            .and_then(|justify_width| {
+                let gaps = content
+                    .iter()
+                    .enumerate()
+                    .filter(|(position, cluster)| is_gap(*position, cluster))
+                    .count();
+                (gaps > 0).then(|| (justify_width - content_width(content)).max(0.0) / gaps as f32)
+            })
+            .unwrap_or(0.0);
 
-        if glyph_cluster.info.whitespace() == Whitespace::Newline {
-            current_line.width = x;
-            x = 0.0;
-            lines.push(mem::take(&mut current_line));
-        }
+        let mut x = 0.0;
+        let mut spans: Vec<(usize, Range<f32>)> = Vec::new();
+        let mut shadow_glyphs: Vec<ShadowGlyph> = Vec::new();
 
-        for glyph in glyph_cluster.glyphs {
-            if let OutlineStyle::Outline {
-                width: outline_width,
-                color: outline_color,
-            } = outline
-            {
-                let stroke_width = outline_width / scale_factor;
-
-                let outline_bitmap = glyph_outline_to_bitmap(glyph.id, stroke_width, &mut scaler);
-                let outline_image = bitmap_to_image(&outline_bitmap, *outline_color);
-
-                if outline_image.width() != 0 && outline_image.height() != 0 {
-                    current_line.glyphs.push(GlyphImage {
-                        offset_x: x + outline_bitmap.placement.left as f32,
-                        offset_y: descent - outline_bitmap.placement.height as f32
-                            + outline_bitmap.placement.top as f32,
-                        offset_z: -0.001,
-                        image: outline_image,
+        for (position, cluster) in bidi::visual_order(&levels)
+            .into_iter()
+            .map(|index| &content[index])
+            .enumerate()
+        {
+            let cluster_x = x;
+            let related_section = &sections[cluster.section];
+            let fill = &related_section.color;
+            let outlines = related_section.outline.layers();
+            let font = &section_fonts[cluster.section][cluster.font];
+            let size = font.size;
+
+            for glyph in &cluster.glyphs {
+                let glyph_x = x + glyph.x;
+                line.layout_glyphs.push(OutlinedTextLayoutGlyph {
+                    position: Vec2::new(glyph_x, glyph.y),
+                    advance: glyph.advance,
+                    line: lines.len(),
+                    section: cluster.section,
+                    byte_range: cluster.range.clone(),
+                    rtl: bidi::is_rtl(cluster.level),
+                });
+                let origin_x = glyph_x.floor();
+                let subpixel_offset =
+                    ((glyph_x - origin_x) * SUBPIXEL_STEPS as f32) as u8 % SUBPIXEL_STEPS;
+                let offset = Vector::new(subpixel_offset as f32 / SUBPIXEL_STEPS as f32, 0.0);
+                let origin_y = glyph.y;
+                let sdf_glyph = matches!(text.render_mode, OutlinedTextRenderMode::Sdf { .. })
+                    && !font.has_color;
+
+                let fill_entry = (!sdf_glyph).then(|| {
+                    let key = GlyphAtlasKey::new(
+                        font.font_ref.key,
+                        font.variations_key,
+                        size,
+                        glyph.id,
+                        0.0,
+                        subpixel_offset,
+                    );
+                    let key = if font.has_color { key.color() } else { key };
+
+                    let entry = glyph_atlas.acquire(key, images, || {
+                        let mut scaler = build_scaler(scale_context, font, size, true);
+                        if font.has_color {
+                            glyph_to_color_bitmap(glyph.id, offset, &mut scaler)
+                        } else {
+                            glyph_to_bitmap(glyph.id, offset, &mut scaler)
+                        }
+                    });
+                    (key, entry)
+                });
+                // Only tinting depends on the rendered fill. Outlines and
+                // shadows of color fonts always use the silhouette, since
+                // bitmap-only fonts have no outlines to stroke.
+                let color_glyph = fill_entry
+                    .as_ref()
+                    .and_then(|(_, entry)| entry.as_ref())
+                    .is_some_and(|entry| entry.color);
+
+                if related_section.shadow.is_some() {
+                    shadow_glyphs.push(ShadowGlyph {
+                        section: cluster.section,
+                        font: cluster.font,
+                        id: glyph.id,
+                        x: origin_x as i32,
+                        y: origin_y.round() as i32,
+                        subpixel_offset,
+                        color: font.has_color,
                     });
                 }
-            }
 
-            let bitmap = glyph_to_bitmap(glyph.id, &mut scaler);
-            let image = bitmap_to_image(&bitmap, color);
+                if sdf_glyph {
+                    let key = GlyphAtlasKey::distance_field(
+                        font.font_ref.key,
+                        font.variations_key,
+                        glyph.id,
+                    );
+
+                    if let Some(entry) = glyph_atlas.acquire(key, images, || {
+                        let mut scaler = build_scaler(scale_context, font, SDF_SIZE, false);
+                        sdf::coverage_to_distance_field(glyph_to_bitmap(
+                            glyph.id,
+                            Vector::new(0.0, 0.0),
+                            &mut scaler,
+                        ))
+                    }) {
+                        // The SDF material carries the section fill, the glyph
+                        // color only carries the fade.
+                        line.glyphs.push(GlyphImage::new(
+                            key,
+                            cluster.section,
+                            Vec3::new(glyph_x, glyph.y, layer_z(0)),
+                            &TextFill::Solid(Color::WHITE),
+                            entry,
+                            size / SDF_SIZE,
+                        ));
+                    }
+
+                    x += glyph.advance;
+                    continue;
+                }
 
-            if image.width() != 0 && image.height() != 0 {
-                current_line.glyphs.push(GlyphImage {
-                    offset_x: x + bitmap.placement.left as f32,
-                    offset_y: descent - bitmap.placement.height as f32
-                        + bitmap.placement.top as f32,
-                    offset_z: 0.0,
-                    image,
-                });
+                for (index, outline) in outlines.iter().enumerate() {
+                    let stroke_width = outline.width / scale_factor;
+                    let key = GlyphAtlasKey::new(
+                        font.font_ref.key,
+                        font.variations_key,
+                        size,
+                        glyph.id,
+                        stroke_width,
+                        subpixel_offset,
+                    )
+                    .stroke_style(outline.stroke_style());
+                    let key = if font.has_color {
+                        key.silhouette()
+                    } else {
+                        key
+                    };
+
+                    if let Some(entry) = glyph_atlas.acquire(key, images, || {
+                        let mut scaler = build_scaler(scale_context, font, size, true);
+                        outline_to_bitmap(
+                            glyph.id,
+                            outline,
+                            stroke_width,
+                            font.has_color,
+                            offset,
+                            &mut scaler,
+                        )
+                    }) {
+                        line.glyphs.push(GlyphImage::new(
+                            key,
+                            cluster.section,
+                            Vec3::new(
+                                origin_x,
+                                origin_y,
+                                outline_layer_z(outline, index, outlines.len()),
+                            ),
+                            &outline.color,
+                            entry,
+                            1.0,
+                        ));
+                    }
+                }
+
+                if let Some((key, Some(entry))) = fill_entry {
+                    let glyph_fill = if color_glyph && !related_section.tint_color_glyphs {
+                        &untinted
+                    } else {
+                        fill
+                    };
+
+                    line.glyphs.push(GlyphImage::new(
+                        key,
+                        cluster.section,
+                        Vec3::new(origin_x, origin_y, layer_z(0)),
+                        glyph_fill,
+                        entry,
+                        1.0,
+                    ));
+                }
+
+                x += glyph.advance;
             }
 
-            x += glyph.advance;
+            if is_gap(position, cluster) {
+                x += gap_spacing;
+            }
+
+            match spans.last_mut() {
+                Some((section, span)) if *section == cluster.section => span.end = x,
+                _ => spans.push((cluster.section, cluster_x..x)),
+            }
         }
-    });
-    current_line.width = x;
-    lines.push(current_line);
 
-    let line_count = lines.len();
-    let text_width = lines.iter().map(|line| line.width).fold(0.0, f32::max);
-    let text_height = descent + ascent + (lines.len() - 1) as f32 * line_height;
+        for run in shadow_glyphs.chunk_by(|a, b| a.section == b.section) {
+            line.glyphs.extend(shadow_image(
+                glyph_atlas,
+                images,
+                scale_context,
+                &section_fonts[run[0].section],
+                run[0].section,
+                &sections[run[0].section],
+                run,
+                scale_factor,
+            ));
+        }
 
-    let anchor_offset = anchor.as_vec();
-    let anchor_offset_x = -anchor_offset.x * text_width - text_width / 2.0;
-    let anchor_offset_y = -anchor_offset.y * text_height - text_height / 2.0;
+        for (section, span) in spans {
+            if let Some(background) = &sections[section].background {
+                line.glyphs.extend(decoration::background_images(
+                    glyph_atlas,
+                    images,
+                    section,
+                    background,
+                    Rect::new(span.start, -line.descent, span.end, line.ascent),
+                    layer_z(SECTION_BACKGROUND_LAYER),
+                    scale_factor,
+                ));
+            }
 
-    for (i, line) in lines.iter_mut().enumerate() {
-        let padding = match text.justify {
-            JustifyOutlinedText::Left => 0.0,
-            JustifyOutlinedText::Center => (text_width - line.width) / 2.0,
-            JustifyOutlinedText::Right => text_width - line.width,
-        };
+            line.glyphs.extend(decoration::decoration_images(
+                glyph_atlas,
+                images,
+                &section_fonts[section][0],
+                section,
+                &sections[section],
+                span,
+                scale_factor,
+            ));
+        }
 
-        for glyph in line.glyphs.iter_mut() {
-            glyph.offset_x += anchor_offset_x + padding;
-            glyph.offset_y += anchor_offset_y + (line_count - i - 1) as f32 * line_height;
+        line.width = x;
+
+        if overflowing {
+            match text.overflow {
+                OverflowMode::Clip => clip_line(glyph_atlas, &mut line, max_width),
+                OverflowMode::FadeOut { length } => {
+                    fade_line(
+                        glyph_atlas,
+                        images,
+                        &mut line,
+                        max_width,
+                        length / scale_factor,
+                    );
+                    clip_line(glyph_atlas, &mut line, max_width);
+                }
+                OverflowMode::Visible | OverflowMode::Ellipsis(_) => {}
+            }
         }
+
+        lines.push(line);
     }
 
-    lines.into_iter().flat_map(|line| line.glyphs).collect()
-}
+    let text_width = lines
+        .iter()
+        .map(|line| line.width)
+        .fold(justify_width.unwrap_or(0.0), f32::max);
 
-fn add_section_to_shaper(
-    shaper: &mut Shaper,
-    section: &OutlinedTextSection,
-    script: Script,
-    charmap: Charmap,
-    section_index: u32,
-) {
-    let mut cluster = CharCluster::new();
-    let mut parser = Parser::new(
-        script,
-        section.value.char_indices().map(|(i, ch)| Token {
-            ch,
-            offset: i as u32,
-            len: ch.len_utf8() as u8,
-            info: ch.properties().into(),
-            data: section_index,
-        }),
+    let mut text_height = stack_lines(&mut lines, font_style.paragraph_spacing / scale_factor);
+
+    if text_height > max_height {
+        match text.overflow {
+            OverflowMode::Clip => {
+                clip_lines(glyph_atlas, &mut lines, -max_height);
+                text_height = max_height;
+            }
+            OverflowMode::FadeOut { length } => {
+                fade_lines(&mut lines, -max_height, length / scale_factor);
+                clip_lines(glyph_atlas, &mut lines, -max_height);
+                text_height = max_height;
+            }
+            OverflowMode::Visible | OverflowMode::Ellipsis(_) => {}
+        }
+    }
+
+    let anchor_offset = anchor.as_vec();
+    let anchor_offset_x = -anchor_offset.x * text_width - text_width / 2.0;
+    let anchor_offset_y = -anchor_offset.y * text_height - text_height / 2.0;
+
+    let mut layout = OutlinedTextLayout {
+        size: Vec2::new(text_width, text_height),
+        ..default()
+    };
+
+    for line in lines.iter_mut() {
+        let padding = match (&text.justify, line.rtl) {
+            (JustifyOutlinedText::Left, _)
+            | (JustifyOutlinedText::Start | JustifyOutlinedText::Justified { .. }, false)
+            | (JustifyOutlinedText::End, true) => 0.0,
+            (JustifyOutlinedText::Center, _) => (text_width - line.width) / 2.0,
+            (JustifyOutlinedText::Right, _)
+            | (JustifyOutlinedText::Start | JustifyOutlinedText::Justified { .. }, true)
+            | (JustifyOutlinedText::End, false) => text_width - line.width,
+        };
+
+        let offset = Vec2::new(
+            anchor_offset_x + padding,
+            anchor_offset_y + text_height + line.baseline,
+        );
+        for glyph in line.glyphs.iter_mut() {
+            glyph.offset_x += offset.x;
+            glyph.offset_y += offset.y;
+        }
+        for glyph in line.layout_glyphs.iter_mut() {
+            glyph.position += offset;
+        }
+
+        let start = layout.glyphs.len();
+        layout.glyphs.append(&mut line.layout_glyphs);
+        layout.lines.push(OutlinedTextLayoutLine {
+            baseline: offset.y,
+            left: offset.x,
+            width: line.width,
+            ascent: line.ascent,
+            descent: line.descent,
+            glyphs: start..layout.glyphs.len(),
+        });
+    }
+
+    let rect = Rect::new(
+        anchor_offset_x,
+        anchor_offset_y,
+        anchor_offset_x + text_width,
+        anchor_offset_y + text_height,
     );
-    while parser.next(&mut cluster) {
-        cluster.map(|ch| charmap.map(ch));
-        shaper.add_cluster(&cluster);
+    let mut glyphs: Vec<GlyphImage> = lines.into_iter().flat_map(|line| line.glyphs).collect();
+
+    if let Some(background) = &text.background {
+        glyphs.extend(decoration::background_images(
+            glyph_atlas,
+            images,
+            0,
+            background,
+            rect,
+            layer_z(TEXT_BACKGROUND_LAYER),
+            scale_factor,
+        ));
     }
+
+    layout.rect = rect;
+    let section_sizes = section_fonts
+        .iter()
+        .map(|fonts| fonts[0].size * scale_factor)
+        .collect();
+
+    Some((
+        OutlinedTextGlyphs {
+            glyphs,
+            rect,
+            section_sizes,
+        },
+        layout,
+    ))
+}
+
+struct ShadowGlyph {
+    section: usize,
+    font: usize,
+    id: GlyphId,
+    x: i32,
+    y: i32,
+    subpixel_offset: u8,
+    color: bool,
 }
 
-fn compose_glyph_images(
+/// Renders the shadow of a run of glyphs from one section as a single bitmap,
+/// so overlapping glyphs don't darken each other's shadows.
+#[allow(clippy::too_many_arguments)]
+fn shadow_image(
+    glyph_atlas: &mut GlyphAtlas,
     images: &mut Assets<Image>,
-    glyph_images: &[GlyphImage],
-) -> Option<OutlinedTextImage> {
-    if glyph_images.is_empty() {
+    scale_context: &mut ScaleContext,
+    fonts: &[FontInstance],
+    section_index: usize,
+    section: &OutlinedTextSection,
+    run: &[ShadowGlyph],
+    scale_factor: f32,
+) -> Option<GlyphImage> {
+    let shadow = section.shadow.as_ref()?;
+    let origin_x = run.first()?.x;
+    let outlines = section.outline.layers();
+    let widest = outlines
+        .iter()
+        .filter(|outline| outline.outer_width() > 0.0)
+        .max_by(|a, b| a.outer_width().total_cmp(&b.outer_width()));
+    let stroke_width = widest.map_or(0.0, |outline| outline.width / scale_factor);
+    let blur_radius = shadow.blur_radius / scale_factor;
+
+    let mut hasher = DefaultHasher::new();
+    stroke_width.to_bits().hash(&mut hasher);
+    widest
+        .map_or(0, OutlineLayer::stroke_style)
+        .hash(&mut hasher);
+    for glyph in run {
+        let font = &fonts[glyph.font];
+        font.font_ref.key.hash(&mut hasher);
+        font.variations_key.hash(&mut hasher);
+        font.size.to_bits().hash(&mut hasher);
+        glyph.id.hash(&mut hasher);
+        (glyph.x - origin_x).hash(&mut hasher);
+        glyph.y.hash(&mut hasher);
+        glyph.subpixel_offset.hash(&mut hasher);
+        glyph.color.hash(&mut hasher);
+    }
+    let key = GlyphAtlasKey::shadow(fonts[0].font_ref.key, hasher.finish(), blur_radius);
+
+    let entry = glyph_atlas.acquire(key, images, || {
+        let bitmaps: Vec<SwashImage> = run
+            .iter()
+            .map(|glyph| {
+                let font = &fonts[glyph.font];
+                let offset = Vector::new(glyph.subpixel_offset as f32 / SUBPIXEL_STEPS as f32, 0.0);
+                let mut scaler = build_scaler(scale_context, font, font.size, true);
+                let mut bitmap = glyph_silhouette(glyph.id, glyph.color, offset, &mut scaler);
+                if let Some(outline) = widest {
+                    let stroke = outline_to_bitmap(
+                        glyph.id,
+                        outline,
+                        stroke_width,
+                        glyph.color,
+                        offset,
+                        &mut scaler,
+                    );
+                    bitmap = coverage::union(&bitmap, &stroke);
+                }
+                bitmap.placement.left += glyph.x - origin_x;
+                bitmap.placement.top += glyph.y;
+                bitmap
+            })
+            .collect();
+
+        coverage::blur(
+            coverage::union_all(&bitmaps.iter().collect::<Vec<_>>()),
+            blur_radius,
+        )
+    })?;
+
+    let shadow_offset = shadow.offset / scale_factor;
+    Some(GlyphImage::new(
+        key,
+        section_index,
+        Vec3::new(
+            origin_x as f32 + shadow_offset.x,
+            shadow_offset.y,
+            layer_z(outlines.len() + 1),
+        ),
+        &TextFill::Solid(shadow.color),
+        entry,
+        1.0,
+    ))
+}
+
+fn apply_spacing(clusters: &mut [ShapedCluster], letter_spacing: f32, word_spacing: f32) {
+    if letter_spacing == 0.0 && word_spacing == 0.0 {
+        return;
+    }
+
+    for cluster in clusters {
+        if cluster.whitespace == Whitespace::Newline {
+            continue;
+        }
+
+        let mut spacing = letter_spacing;
+        if cluster.whitespace.is_space_or_nbsp() {
+            spacing += word_spacing;
+        }
+
+        if let Some(glyph) = cluster.glyphs.last_mut() {
+            glyph.advance += spacing;
+            cluster.advance += spacing;
+            cluster.letter_spacing = letter_spacing;
+        }
+    }
+}
+
+/// Removes the letter spacing after the last visible cluster of each line,
+/// so spacing only ever falls between clusters.
+fn trim_letter_spacing(clusters: &mut [ShapedCluster], line_ranges: &[Range<usize>]) {
+    for line_range in line_ranges {
+        let line_clusters = &mut clusters[line_range.clone()];
+        let end = trim_trailing_whitespace(line_clusters).len();
+        let Some(cluster) = line_clusters[..end].last_mut() else {
+            continue;
+        };
+
+        if let Some(glyph) = cluster.glyphs.last_mut() {
+            glyph.advance -= cluster.letter_spacing;
+        }
+        cluster.advance -= cluster.letter_spacing;
+        cluster.letter_spacing = 0.0;
+    }
+}
+
+fn text_section_fonts<'a>(
+    fonts: &'a Assets<OutlinedFont>,
+    text: &OutlinedText,
+    font_size: f32,
+    scale_factor: f32,
+) -> Option<Vec<Vec<FontInstance<'a>>>> {
+    let font_style = &text.font_style;
+    // Fitted sizes scale explicitly sized sections along with the rest.
+    let scale = if font_style.size > 0.0 {
+        font_size / font_style.size
+    } else {
+        1.0
+    };
+
+    text.sections
+        .iter()
+        .map(|section| {
+            let size = section.size.map_or(font_size, |size| size * scale) / scale_factor;
+            let variations = font_style.variation_settings(section);
+
+            let primary = fonts.get(section.font.as_ref().unwrap_or(&font_style.font))?;
+
+            // Fallbacks that are still loading only matter once they arrive.
+            Some(
+                std::iter::once(primary)
+                    .chain(
+                        font_style
+                            .fallbacks
+                            .iter()
+                            .filter_map(|handle| fonts.get(handle)),
+                    )
+                    .map(|font| FontInstance::new(font.as_ref(), &variations, size))
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+fn fit_font_size(
+    shape_context: &mut ShapeContext,
+    fonts: &Assets<OutlinedFont>,
+    text: &OutlinedText,
+    bounds: Option<&OutlinedTextBounds>,
+    auto_size: AutoSize,
+    scale_factor: f32,
+) -> Option<f32> {
+    if text.sections.is_empty() {
+        return Some(auto_size.max_size);
+    }
+
+    let bounds = bounds.copied().unwrap_or_default();
+    let mut fits = |size: f32| {
+        let section_fonts = text_section_fonts(fonts, text, size, scale_factor)?;
+        Some(
+            measure_text(
+                shape_context,
+                &section_fonts,
+                text,
+                bounds.width,
+                scale_factor,
+            )
+            .is_some_and(|size| size.x <= bounds.width && size.y <= bounds.height),
+        )
+    };
+
+    if fits(auto_size.max_size)? {
+        return Some(auto_size.max_size);
+    }
+    if !fits(auto_size.min_size)? {
+        return Some(auto_size.min_size);
+    }
+
+    let mut low = auto_size.min_size;
+    let mut high = auto_size.max_size;
+    for _ in 0..AUTO_SIZE_STEPS {
+        let size = (low + high) / 2.0;
+        if fits(size)? {
+            low = size;
+        } else {
+            high = size;
+        }
+    }
+
+    Some(low)
+}
+
+fn measure_text(
+    shape_context: &mut ShapeContext,
+    section_fonts: &[Vec<FontInstance>],
+    text: &OutlinedText,
+    max_width: f32,
+    scale_factor: f32,
+) -> Option<Vec2> {
+    let font_style = &text.font_style;
+    let mut clusters = shape_text(shape_context, section_fonts, text);
+    apply_spacing(
+        &mut clusters,
+        font_style.letter_spacing / scale_factor,
+        font_style.word_spacing / scale_factor,
+    );
+
+    let line_ranges = break_lines(&clusters, max_width);
+    if text
+        .max_lines
+        .is_some_and(|max_lines| line_ranges.len() > max_lines)
+    {
         return None;
     }
+    trim_letter_spacing(&mut clusters, &line_ranges);
+
+    let (lines, height) = measure_lines(
+        &clusters,
+        &line_ranges,
+        section_fonts,
+        font_style,
+        scale_factor,
+    );
+    let width = lines.iter().map(|line| line.width).fold(0.0, f32::max);
+
+    Some(Vec2::new(width, height))
+}
 
-    let z_index = glyph_images.first().unwrap().offset_z;
+fn ends_paragraph(clusters: &[ShapedCluster], line_range: &Range<usize>) -> bool {
+    clusters
+        .get(line_range.end)
+        .is_some_and(|cluster| cluster.whitespace == Whitespace::Newline)
+}
 
-    let mut x_min = f32::INFINITY;
-    let mut x_max = f32::NEG_INFINITY;
-    let mut y_min = f32::INFINITY;
-    let mut y_max = f32::NEG_INFINITY;
+fn set_line_metrics(
+    line: &mut OutlinedGlyphLine,
+    line_clusters: &[ShapedCluster],
+    section_fonts: &[Vec<FontInstance>],
+    line_height: LineHeight,
+    scale_factor: f32,
+) {
+    let mut line_size = 0.0f32;
+
+    if line_clusters.is_empty() {
+        let font = &section_fonts[0][0];
+        line.ascent = font.metrics.ascent;
+        line.descent = font.metrics.descent;
+        line.leading = font.metrics.leading;
+        line_size = font.size;
+    }
 
-    for glyph in glyph_images {
-        let x = glyph.offset_x;
-        let y = glyph.offset_y;
-        let width = glyph.image.width() as f32;
-        let height = glyph.image.height() as f32;
+    for cluster in line_clusters {
+        let font = &section_fonts[cluster.section][cluster.font];
+        line.ascent = line.ascent.max(font.metrics.ascent);
+        line.descent = line.descent.max(font.metrics.descent);
+        line.leading = line.leading.max(font.metrics.leading);
+        line_size = line_size.max(font.size);
+    }
 
-        x_min = x_min.min(x);
-        x_max = x_max.max(x + width);
-        y_min = y_min.min(y);
-        y_max = y_max.max(y + height);
+    if let Some(line_height) = line_height.resolve(line_size, scale_factor) {
+        let half_leading = (line_height - line.ascent - line.descent) / 2.0;
+        line.ascent += half_leading;
+        line.descent += half_leading;
+        line.leading = 0.0;
     }
+}
+
+fn measure_lines(
+    clusters: &[ShapedCluster],
+    line_ranges: &[Range<usize>],
+    section_fonts: &[Vec<FontInstance>],
+    font_style: &OutlinedFontStyle,
+    scale_factor: f32,
+) -> (Vec<OutlinedGlyphLine>, f32) {
+    let mut lines: Vec<OutlinedGlyphLine> = line_ranges
+        .iter()
+        .map(|line_range| {
+            let line_clusters = &clusters[line_range.clone()];
+            let mut line = OutlinedGlyphLine {
+                width: content_width(trim_trailing_whitespace(line_clusters)),
+                paragraph_end: ends_paragraph(clusters, line_range),
+                ..default()
+            };
+            set_line_metrics(
+                &mut line,
+                line_clusters,
+                section_fonts,
+                font_style.line_height,
+                scale_factor,
+            );
+            line
+        })
+        .collect();
+
+    let height = stack_lines(&mut lines, font_style.paragraph_spacing / scale_factor);
+    (lines, height)
+}
 
-    let total_width = (x_max - x_min).ceil() as u32;
-    let total_height = (y_max - y_min).ceil() as u32;
+fn fitting_lines(
+    clusters: &[ShapedCluster],
+    line_ranges: &[Range<usize>],
+    section_fonts: &[Vec<FontInstance>],
+    font_style: &OutlinedFontStyle,
+    max_height: f32,
+    scale_factor: f32,
+) -> usize {
+    let (lines, _) = measure_lines(
+        clusters,
+        line_ranges,
+        section_fonts,
+        font_style,
+        scale_factor,
+    );
+
+    lines
+        .iter()
+        .take_while(|line| line.descent - line.baseline <= max_height)
+        .count()
+        .max(1)
+}
 
-    let mut data = vec![0; (total_width * total_height * 4) as usize];
+fn stack_lines(lines: &mut [OutlinedGlyphLine], paragraph_spacing: f32) -> f32 {
+    let mut baseline = 0.0;
+    let mut previous_line: Option<&OutlinedGlyphLine> = None;
+    for line in lines.iter_mut() {
+        baseline -= previous_line.map_or(0.0, |previous| {
+            let spacing = if previous.paragraph_end {
+                paragraph_spacing
+            } else {
+                0.0
+            };
+            previous.descent + previous.leading + spacing
+        });
+        baseline -= line.ascent;
+        line.baseline = baseline;
+        previous_line = Some(line);
+    }
 
-    for glyph in glyph_images {
-        let width = glyph.image.width();
-        let height = glyph.image.height();
+    previous_line.map_or(0.0, |line| line.descent - line.baseline)
+}
 
-        let dest_x = (glyph.offset_x - x_min).round() as u32;
-        let dest_y = total_height - height - (glyph.offset_y - y_min).round() as u32;
+#[allow(clippy::too_many_arguments)]
+fn ellipsize(
+    shape_context: &mut ShapeContext,
+    section_fonts: &[Vec<FontInstance>],
+    text: &OutlinedText,
+    content: &[ShapedCluster],
+    ellipsis: &str,
+    max_width: f32,
+    paragraph_level: u8,
+    scale_factor: f32,
+) -> Vec<ShapedCluster> {
+    let mut end = content.len();
+
+    loop {
+        let section = content[..end]
+            .last()
+            .or(content.first())
+            .map_or(0, |cluster| cluster.section);
+
+        let ellipsis_text = OutlinedText {
+            sections: vec![OutlinedTextSection {
+                value: ellipsis.to_string(),
+                ..text.sections[section].clone()
+            }],
+            font_style: text.font_style.clone(),
+            direction: text.direction,
+            ..default()
+        };
+        let mut ellipsis_clusters = shape_text(
+            shape_context,
+            &section_fonts[section..=section],
+            &ellipsis_text,
+        );
+        apply_spacing(
+            &mut ellipsis_clusters,
+            text.font_style.letter_spacing / scale_factor,
+            text.font_style.word_spacing / scale_factor,
+        );
+        let ellipsis_range = 0..ellipsis_clusters.len();
+        trim_letter_spacing(&mut ellipsis_clusters, &[ellipsis_range]);
 
-        for source_y in 0..height {
-            for source_x in 0..width {
-                let src_index = (source_y * width + source_x) as usize * 4;
-                let dest_index =
-                    ((dest_y + source_y) * total_width + dest_x + source_x) as usize * 4;
+        let available = max_width - content_width(&ellipsis_clusters);
+        let mut width = 0.0;
+        let fitting = content[..end]
+            .iter()
+            .take_while(|cluster| {
+                width += cluster.advance;
+                width <= available
+            })
+            .count();
+        let kept = trim_trailing_whitespace(&content[..fitting]).len();
+
+        if kept == end || content[..kept].last().map(|cluster| cluster.section) == Some(section) {
+            let mut clusters = content[..kept].to_vec();
+            let position = clusters.last().map_or(0, |cluster| cluster.range.end);
+            clusters.extend(ellipsis_clusters.into_iter().map(|cluster| ShapedCluster {
+                section,
+                range: position..position,
+                level: paragraph_level,
+                paragraph_level,
+                ..cluster
+            }));
+            return clusters;
+        }
 
-                let src = &glyph.image.data[src_index..src_index + 4];
-                let dest = &mut data[dest_index..dest_index + 4];
+        end = kept;
+    }
+}
 
-                let alpha =
-                    (255.0 - ((255.0 - src[3] as f32) * (255.0 - dest[3] as f32)) / 255.0) as u8;
-                let red = ((src[0] as f32 * (255.0 - dest[3] as f32)
-                    + dest[0] as f32 * (255.0 - src[3] as f32))
-                    / 255.0) as u8;
-                let green = ((src[1] as f32 * (255.0 - dest[3] as f32)
-                    + dest[1] as f32 * (255.0 - src[3] as f32))
-                    / 255.0) as u8;
-                let blue = ((src[2] as f32 * (255.0 - dest[3] as f32)
-                    + dest[2] as f32 * (255.0 - src[3] as f32))
-                    / 255.0) as u8;
+/// Cuts the line to `max_width`, dropping its logical end: the right side of
+/// left-to-right lines and the left side of right-to-left ones.
+fn clip_line(glyph_atlas: &mut GlyphAtlas, line: &mut OutlinedGlyphLine, max_width: f32) {
+    if !line.rtl {
+        line.width = line.width.min(max_width);
+        line.glyphs.retain_mut(|glyph| {
+            let visible = max_width - glyph.offset_x;
+            if visible <= 0.0 {
+                glyph_atlas.release(&glyph.key);
+                return false;
+            }
 
-                dest[0] = red;
-                dest[1] = green;
-                dest[2] = blue;
-                dest[3] = alpha;
+            if visible < glyph.size.x {
+                glyph.rect.max.x = glyph.rect.min.x + glyph.rect.width() * visible / glyph.size.x;
+                glyph.size.x = visible;
             }
+            true
+        });
+        return;
+    }
+
+    let min_x = line.width - max_width;
+    if min_x <= 0.0 {
+        return;
+    }
+
+    line.width = max_width;
+    line.glyphs.retain_mut(|glyph| {
+        let hidden = min_x - glyph.offset_x;
+        if hidden >= glyph.size.x {
+            glyph_atlas.release(&glyph.key);
+            return false;
+        }
+
+        if hidden > 0.0 {
+            glyph.rect.min.x += glyph.rect.width() * hidden / glyph.size.x;
+            glyph.offset_x = min_x;
+            glyph.size.x -= hidden;
         }
+        glyph.offset_x -= min_x;
+        true
+    });
+    for glyph in &mut line.layout_glyphs {
+        glyph.position.x -= min_x;
     }
+}
 
-    let image = Image::new(
-        Extent3d {
-            width: total_width,
-            height: total_height,
-            depth_or_array_layers: 1,
-        },
-        TextureDimension::D2,
-        data,
-        TextureFormat::Rgba8UnormSrgb,
-        RenderAssetUsages::default(),
-    );
+/// Fades the line out over `length` towards the edge [`clip_line`] cuts it at.
+fn fade_line(
+    glyph_atlas: &mut GlyphAtlas,
+    images: &mut Assets<Image>,
+    line: &mut OutlinedGlyphLine,
+    max_width: f32,
+    length: f32,
+) {
+    let edge = if line.rtl {
+        (line.width - max_width).max(0.0)
+    } else {
+        max_width.min(line.width)
+    };
+    let fade = if line.rtl {
+        edge..edge + length
+    } else {
+        edge - length..edge
+    };
+
+    // Stretched decoration strips take one color per quad, so the part inside
+    // the fade is split into short steps that fade one after another.
+    let mut glyphs = Vec::with_capacity(line.glyphs.len());
+    for glyph in line.glyphs.drain(..) {
+        if glyph.key.kind != GlyphAtlasKind::Decoration || glyph.size.x <= glyph.rect.width() {
+            glyphs.push(glyph);
+            continue;
+        }
 
-    Some(OutlinedTextImage {
-        x: x_min,
-        y: y_min,
-        z: z_index,
-        image: images.add(image),
-    })
+        let start = glyph.offset_x;
+        let end = start + glyph.size.x;
+        let (from, to) = (fade.start.max(start), fade.end.min(end));
+        let mut cuts = vec![start];
+        let mut cut = from;
+        while cut < to {
+            if cut > start {
+                cuts.push(cut);
+            }
+            cut += FADE_STEP;
+        }
+        if from < to && to < end {
+            cuts.push(to);
+        }
+        cuts.push(end);
+
+        for (index, piece) in cuts.windows(2).enumerate() {
+            if index > 0 {
+                glyph_atlas.acquire(glyph.key, images, SwashImage::new);
+            }
+            glyphs.push(GlyphImage {
+                offset_x: piece[0],
+                size: Vec2::new(piece[1] - piece[0], glyph.size.y),
+                ..glyph.clone()
+            });
+        }
+    }
+    line.glyphs = glyphs;
+
+    for glyph in line.glyphs.iter_mut().filter(|glyph| fades_out(glyph)) {
+        let center = glyph.offset_x + glyph.size.x / 2.0;
+        let visible = if line.rtl {
+            center - edge
+        } else {
+            edge - center
+        };
+        glyph.color.alpha *= (visible / length.max(f32::EPSILON)).clamp(0.0, 1.0);
+    }
+}
+
+/// Backgrounds keep their opacity when text fades out, so the box they draw
+/// stays intact behind it.
+fn fades_out(glyph: &GlyphImage) -> bool {
+    glyph.key.kind != GlyphAtlasKind::Background
+}
+
+fn clip_lines(glyph_atlas: &mut GlyphAtlas, lines: &mut Vec<OutlinedGlyphLine>, min_y: f32) {
+    lines.retain_mut(|line| {
+        let min_y = min_y - line.baseline;
+        let visible_line = line.ascent > min_y;
+
+        line.glyphs.retain_mut(|glyph| {
+            let hidden = min_y - glyph.offset_y;
+            if !visible_line || hidden >= glyph.size.y {
+                glyph_atlas.release(&glyph.key);
+                return false;
+            }
+
+            if hidden > 0.0 {
+                glyph.rect.max.y -= glyph.rect.height() * hidden / glyph.size.y;
+                glyph.offset_y = min_y;
+                glyph.size.y -= hidden;
+            }
+            true
+        });
+
+        visible_line
+    });
+}
+
+fn fade_lines(lines: &mut [OutlinedGlyphLine], min_y: f32, length: f32) {
+    for line in lines {
+        for glyph in line.glyphs.iter_mut().filter(|glyph| fades_out(glyph)) {
+            let center = line.baseline + glyph.offset_y + glyph.size.y / 2.0;
+            glyph.color.alpha *= ((center - min_y) / length.max(f32::EPSILON)).clamp(0.0, 1.0);
+        }
+    }
+}
+
+fn trim_trailing_whitespace(clusters: &[ShapedCluster]) -> &[ShapedCluster] {
+    let end = clusters
+        .iter()
+        .rposition(|cluster| !is_trailing_whitespace(cluster))
+        .map_or(0, |index| index + 1);
+
+    &clusters[..end]
+}
+
+fn content_width(clusters: &[ShapedCluster]) -> f32 {
+    clusters.iter().map(|cluster| cluster.advance).sum()
+}
+
+fn is_trailing_whitespace(cluster: &ShapedCluster) -> bool {
+    cluster.whitespace.is_space_or_nbsp() || cluster.whitespace == Whitespace::Tab
+}
+
+fn line_levels(clusters: &[ShapedCluster], paragraph_level: u8) -> Vec<u8> {
+    let mut levels: Vec<u8> = clusters.iter().map(|cluster| cluster.level).collect();
+    let mut trailing = true;
+
+    // L1: separators, and any whitespace or isolate controls before them or at
+    // the end of the line, go back to the paragraph level.
+    for (level, cluster) in levels.iter_mut().zip(clusters).rev() {
+        match cluster.bidi_class {
+            BidiClass::S | BidiClass::B => trailing = true,
+            BidiClass::WS
+            | BidiClass::FSI
+            | BidiClass::LRI
+            | BidiClass::RLI
+            | BidiClass::PDI
+            | BidiClass::BN
+            | BidiClass::LRE
+            | BidiClass::RLE
+            | BidiClass::LRO
+            | BidiClass::RLO
+            | BidiClass::PDF => {}
+            _ => trailing = false,
+        }
+
+        if trailing {
+            *level = paragraph_level;
+        }
+    }
+
+    levels
+}
+
+fn break_lines(clusters: &[ShapedCluster], max_width: f32) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut width = 0.0;
+    let mut break_opportunity = None;
+
+    for (index, cluster) in clusters.iter().enumerate() {
+        if cluster.whitespace == Whitespace::Newline {
+            lines.push(start..index);
+            start = index + 1;
+            width = 0.0;
+            break_opportunity = None;
+            continue;
+        }
+
+        if index > start && cluster.boundary >= Boundary::Line {
+            break_opportunity = Some(index);
+        }
+
+        if index > start
+            && !cluster.whitespace.is_space_or_nbsp()
+            && width + cluster.advance - cluster.letter_spacing > max_width
+        {
+            let line_end = break_opportunity.take().unwrap_or(index);
+
+            lines.push(start..line_end);
+            start = line_end;
+            width = clusters[start..index]
+                .iter()
+                .map(|cluster| cluster.advance)
+                .sum();
+        }
+
+        width += cluster.advance;
+    }
+
+    lines.push(start..clusters.len());
+
+    lines
+}
+
+fn shape_text(
+    shape_context: &mut ShapeContext,
+    section_fonts: &[Vec<FontInstance>],
+    text: &OutlinedText,
+) -> Vec<ShapedCluster> {
+    let sections = &text.sections;
+    let chars = sections.iter().enumerate().flat_map(|(index, section)| {
+        section
+            .value
+            .char_indices()
+            .map(move |(offset, ch)| (index, offset, ch))
+    });
+
+    let token_sections: Vec<usize> = chars.clone().map(|(index, _, _)| index).collect();
+    let tokens: Vec<Token> = chars
+        .clone()
+        .zip(analyze(chars.map(|(_, _, ch)| ch)))
+        .enumerate()
+        .map(
+            |(token_index, ((_, offset, ch), (properties, boundary)))| Token {
+                ch,
+                offset: offset as u32,
+                len: ch.len_utf8() as u8,
+                info: CharInfo::new(properties, boundary),
+                data: token_index as u32,
+            },
+        )
+        .collect();
+
+    let value: String = tokens.iter().map(|token| token.ch).collect();
+    let (levels, paragraph_levels) = bidi::resolve_levels(&value, text.direction);
+    let mut clusters = Vec::new();
+
+    for (script, level, run) in itemize_runs(&tokens, &levels, &token_sections, sections) {
+        let rtl = bidi::is_rtl(level);
+        let section = token_sections[run.start];
+        let fonts = &section_fonts[section];
+        let features = text.font_style.feature_settings(&sections[section]);
+        let mut cluster = CharCluster::new();
+        let mut parser = Parser::new(script, tokens[run].iter().copied());
+        let mut shaper: Option<(usize, Shaper)> = None;
+
+        while parser.next(&mut cluster) {
+            let font = select_font(&mut cluster, fonts, rtl);
+
+            if shaper.as_ref().map(|(current, _)| *current) != Some(font) {
+                if let Some((current, shaper)) = shaper.take() {
+                    collect_clusters(
+                        shaper,
+                        current,
+                        &tokens,
+                        &token_sections,
+                        &levels,
+                        &paragraph_levels,
+                        &mut clusters,
+                    );
+                }
+
+                let direction = if rtl {
+                    Direction::RightToLeft
+                } else {
+                    Direction::LeftToRight
+                };
+                let run_shaper = shape_context
+                    .builder(fonts[font].font_ref)
+                    .normalized_coords(&fonts[font].coords)
+                    .features(&features)
+                    .script(script)
+                    .direction(direction)
+                    .size(fonts[font].size)
+                    .build();
+                shaper = Some((font, run_shaper));
+            }
+
+            if let Some((_, shaper)) = &mut shaper {
+                shaper.add_cluster(&cluster);
+            }
+        }
+
+        if let Some((current, shaper)) = shaper {
+            collect_clusters(
+                shaper,
+                current,
+                &tokens,
+                &token_sections,
+                &levels,
+                &paragraph_levels,
+                &mut clusters,
+            );
+        }
+    }
+
+    clusters
+}
+
+fn collect_clusters(
+    shaper: Shaper,
+    font: usize,
+    tokens: &[Token],
+    token_sections: &[usize],
+    levels: &[u8],
+    paragraph_levels: &[u8],
+    clusters: &mut Vec<ShapedCluster>,
+) {
+    shaper.shape_with(|glyph_cluster| {
+        let token = glyph_cluster.data as usize;
+
+        clusters.push(ShapedCluster {
+            section: token_sections[token],
+            font,
+            level: levels[token],
+            paragraph_level: paragraph_levels[token],
+            bidi_class: tokens[token].info.bidi_class(),
+            glyphs: glyph_cluster.glyphs.to_vec(),
+            range: glyph_cluster.source.to_range(),
+            advance: glyph_cluster.advance(),
+            letter_spacing: 0.0,
+            boundary: glyph_cluster.info.boundary(),
+            whitespace: glyph_cluster.info.whitespace(),
+        });
+    });
+}
+
+fn itemize_runs(
+    tokens: &[Token],
+    levels: &[u8],
+    token_sections: &[usize],
+    sections: &[OutlinedTextSection],
+) -> Vec<(Script, u8, Range<usize>)> {
+    let is_real_script =
+        |script: &Script| !matches!(script, Script::Common | Script::Inherited | Script::Unknown);
+
+    let mut current_script = tokens
+        .iter()
+        .map(|token| token.ch.script())
+        .find(is_real_script)
+        .unwrap_or(Script::Latin);
+    let mut current_level = levels.first().copied().unwrap_or(0);
+
+    let mut runs = Vec::new();
+    let mut start = 0;
+
+    for (index, token) in tokens.iter().enumerate() {
+        let script = token.ch.script();
+        let script_changed = is_real_script(&script) && script != current_script;
+
+        let section_changed = index > 0
+            && !sections[token_sections[index]].shapes_like(&sections[token_sections[index - 1]]);
+
+        if script_changed || section_changed || levels[index] != current_level {
+            if index > start {
+                runs.push((current_script, current_level, start..index));
+            }
+            start = index;
+            if is_real_script(&script) {
+                current_script = script;
+            }
+            current_level = levels[index];
+        }
+    }
+
+    if start < tokens.len() {
+        runs.push((current_script, current_level, start..tokens.len()));
+    }
+
+    runs
+}
+
+fn select_font(cluster: &mut CharCluster, fonts: &[FontInstance], rtl: bool) -> usize {
+    let mut selected = 0;
+
+    for (index, font) in fonts.iter().enumerate() {
+        let charmap = font.font_ref.charmap();
+
+        match cluster.map(|ch| {
+            let ch = if rtl { ch.mirror().unwrap_or(ch) } else { ch };
+            charmap.map(ch)
+        }) {
+            Status::Complete => return index,
+            Status::Keep => selected = index,
+            Status::Discard => {}
+        }
+    }
+
+    selected
 }
 
 pub fn extract_outlined_text(
@@ -511,49 +2244,1825 @@ pub fn extract_outlined_text(
     outlined_glyph_images: Extract<Res<OutlinedTextImages>>,
 ) {
     for (original_entity, global_transform) in query.iter() {
-        if let Some(glyph_images) = outlined_glyph_images.cache.get(&original_entity) {
-            for glyph_image in glyph_images {
-                let entity = commands.spawn_empty().id();
-
-                let transform = GlobalTransform::from_translation(Vec3 {
-                    x: glyph_image.x,
-                    y: glyph_image.y,
-                    z: glyph_image.z,
-                });
+        if let Some(glyphs) = outlined_glyph_images.cache.get(&original_entity) {
+            for glyph_image in &glyphs.glyphs {
+                if glyph_image.key.kind == GlyphAtlasKind::DistanceField
+                    || glyph_image.fill.is_some()
+                {
+                    continue;
+                }
 
+                let entity = commands.spawn_empty().id();
                 extracted_sprites.sprites.insert(
                     entity,
-                    ExtractedSprite {
-                        transform: *global_transform * transform,
-                        color: LinearRgba::WHITE,
-                        rect: None,
-                        custom_size: None,
-                        image_handle_id: glyph_image.image.id(),
-                        flip_x: false,
-                        flip_y: false,
-                        anchor: Anchor::BottomLeft.as_vec(),
-                        original_entity: Some(original_entity),
-                    },
+                    glyph_sprite(glyph_image, global_transform, original_entity),
                 );
             }
         }
     }
 }
 
-pub struct OutlinedTextPlugin;
+/// Glyph images may be drawn larger or smaller than their atlas rect, so the
+/// sprite is always given the glyph image size.
+fn glyph_sprite(
+    glyph_image: &GlyphImage,
+    global_transform: &GlobalTransform,
+    original_entity: Entity,
+) -> ExtractedSprite {
+    let transform = GlobalTransform::from_translation(Vec3 {
+        x: glyph_image.offset_x,
+        y: glyph_image.offset_y,
+        z: glyph_image.offset_z,
+    });
 
-impl Plugin for OutlinedTextPlugin {
-    fn build(&self, app: &mut App) {
-        app.insert_resource(OutlinedTextImages::default())
-            .init_asset::<OutlinedFont>()
-            .init_asset_loader::<OutlinedFontLoader>()
-            .add_systems(PostUpdate, create_missing_text);
+    ExtractedSprite {
+        transform: *global_transform * transform,
+        color: glyph_image.color,
+        rect: Some(glyph_image.rect),
+        custom_size: Some(glyph_image.size),
+        image_handle_id: glyph_image.image.id(),
+        flip_x: false,
+        flip_y: false,
+        anchor: Anchor::BottomLeft.as_vec(),
+        original_entity: Some(original_entity),
+    }
+}
 
-        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
-            render_app.add_systems(
-                ExtractSchedule,
-                extract_outlined_text.after(SpriteSystem::ExtractSprites),
+#[allow(clippy::type_complexity)]
+pub fn update_outlined_text_meshes(
+    mut commands: Commands,
+    text_query: Query<&OutlinedText>,
+    images: Res<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut sdf_materials: ResMut<Assets<OutlinedTextSdfMaterial>>,
+    mut fill_materials: ResMut<Assets<OutlinedTextFillMaterial>>,
+    mut outlined_text_images: ResMut<OutlinedTextImages>,
+) {
+    let OutlinedTextImages {
+        cache,
+        mesh_entities,
+        pending_meshes,
+    } = &mut *outlined_text_images;
+
+    for entity in pending_meshes.drain(..) {
+        for mesh_entity in mesh_entities.remove(&entity).into_iter().flatten() {
+            if let Some(mesh_entity) = commands.get_entity(mesh_entity) {
+                mesh_entity.despawn_recursive();
+            }
+        }
+
+        let (Ok(text), Some(glyphs)) = (text_query.get(entity), cache.get(&entity)) else {
+            continue;
+        };
+
+        let mut spawned = Vec::new();
+
+        let mut fill_batches: Vec<(&Handle<Image>, &TextFill, Vec<&GlyphImage>)> = Vec::new();
+        for glyph_image in glyphs
+            .glyphs
+            .iter()
+            .filter(|glyph_image| glyph_image.key.kind != GlyphAtlasKind::DistanceField)
+        {
+            let Some(fill) = &glyph_image.fill else {
+                continue;
+            };
+
+            match fill_batches
+                .iter_mut()
+                .find(|(image, other, _)| **image == glyph_image.image && *other == fill)
+            {
+                Some((_, _, batch)) => batch.push(glyph_image),
+                None => fill_batches.push((&glyph_image.image, fill, vec![glyph_image])),
+            }
+        }
+
+        for (image, fill, batch) in fill_batches {
+            let Some(page) = images.get(image) else {
+                continue;
+            };
+
+            let mesh = glyph_quads_mesh(&batch, page.size_f32(), fill.mapping(), glyphs.rect);
+            spawn_text_mesh(
+                &mut commands,
+                entity,
+                &mut spawned,
+                MaterialMesh2dBundle {
+                    mesh: meshes.add(mesh).into(),
+                    material: fill_materials
+                        .add(OutlinedTextFillMaterial::new(fill, image.clone())),
+                    ..default()
+                },
             );
         }
+
+        if let OutlinedTextRenderMode::Sdf { softness } = text.render_mode {
+            let mut sdf_batches: Vec<(&Handle<Image>, usize, Vec<&GlyphImage>)> = Vec::new();
+            for glyph_image in glyphs
+                .glyphs
+                .iter()
+                .filter(|glyph_image| glyph_image.key.kind == GlyphAtlasKind::DistanceField)
+            {
+                match sdf_batches.iter_mut().find(|(image, section, _)| {
+                    **image == glyph_image.image && *section == glyph_image.section
+                }) {
+                    Some((_, _, batch)) => batch.push(glyph_image),
+                    None => sdf_batches.push((
+                        &glyph_image.image,
+                        glyph_image.section,
+                        vec![glyph_image],
+                    )),
+                }
+            }
+
+            for (image, section_index, batch) in sdf_batches {
+                let Some(page) = images.get(image) else {
+                    continue;
+                };
+                let section = &text.sections[section_index];
+                let section_size = glyphs.section_sizes[section_index];
+                let mut layer_meshes: HashMap<TextFillMapping, Handle<Mesh>> = HashMap::new();
+
+                let outlines = section.outline.layers();
+                let fill = OutlinedTextSdfMaterial {
+                    color: LinearRgba::WHITE,
+                    outline_color: LinearRgba::WHITE,
+                    outline_width: 0.0,
+                    offset: 0.0,
+                    softness,
+                    spread: SDF_SPREAD,
+                    fill_kind: 0,
+                    fill_from: LinearRgba::WHITE,
+                    fill_to: LinearRgba::WHITE,
+                    fill_gradient: Vec4::ZERO,
+                    texture: image.clone(),
+                    fill_texture: None,
+                }
+                .with_fill(&section.color);
+
+                let layers = outlines.iter().enumerate().map(|(index, outline)| {
+                    let max_width = match outline.placement {
+                        OutlinePlacement::Center => SDF_SPREAD * 2.0,
+                        OutlinePlacement::Outside | OutlinePlacement::Inside => SDF_SPREAD,
+                    };
+                    let mut width = outline.width * SDF_SIZE / section_size;
+                    if width > max_width {
+                        warn_once!(
+                            "outline width {} is wider than the distance field spread at size {} \
+                             and will be clamped, use the bitmap render mode for wider outlines",
+                            outline.width,
+                            section_size
+                        );
+                        width = max_width;
+                    }
+                    let material = OutlinedTextSdfMaterial {
+                        outline_width: width / 2.0,
+                        ..fill.clone()
+                    }
+                    .with_fill(&outline.color);
+
+                    let material = match outline.placement {
+                        OutlinePlacement::Center => material,
+                        OutlinePlacement::Outside => OutlinedTextSdfMaterial {
+                            outline_width: width,
+                            ..material
+                        },
+                        OutlinePlacement::Inside => OutlinedTextSdfMaterial {
+                            color: LinearRgba::NONE,
+                            outline_width: width,
+                            offset: width,
+                            ..material
+                        },
+                    };
+
+                    let z = outline_layer_z(outline, index, outlines.len());
+                    (z, outline.color.mapping(), material)
+                });
+
+                for (z, mapping, material) in
+                    std::iter::once((layer_z(0), section.color.mapping(), fill.clone()))
+                        .chain(layers)
+                {
+                    let mesh = layer_meshes
+                        .entry(mapping)
+                        .or_insert_with(|| {
+                            meshes.add(glyph_quads_mesh(
+                                &batch,
+                                page.size_f32(),
+                                mapping,
+                                glyphs.rect,
+                            ))
+                        })
+                        .clone();
+
+                    spawn_text_mesh(
+                        &mut commands,
+                        entity,
+                        &mut spawned,
+                        MaterialMesh2dBundle {
+                            mesh: mesh.into(),
+                            material: sdf_materials.add(material),
+                            transform: Transform::from_xyz(0.0, 0.0, z),
+                            ..default()
+                        },
+                    );
+                }
+            }
+        }
+
+        mesh_entities.insert(entity, spawned);
+    }
+}
+
+fn spawn_text_mesh(
+    commands: &mut Commands,
+    parent: Entity,
+    spawned: &mut Vec<Entity>,
+    bundle: impl Bundle,
+) {
+    let mesh_entity = commands.spawn(bundle).id();
+    commands.entity(parent).add_child(mesh_entity);
+    spawned.push(mesh_entity);
+}
+
+fn glyph_quads_mesh(
+    glyph_images: &[&GlyphImage],
+    page_size: Vec2,
+    mapping: TextFillMapping,
+    block: Rect,
+) -> Mesh {
+    let mut positions = Vec::with_capacity(glyph_images.len() * 4);
+    let mut uvs = Vec::with_capacity(glyph_images.len() * 4);
+    let mut fill_uvs = Vec::with_capacity(glyph_images.len() * 4);
+    let mut colors = Vec::with_capacity(glyph_images.len() * 4);
+    let block_size = block.size().max(Vec2::ONE);
+    let mut indices = Vec::with_capacity(glyph_images.len() * 6);
+
+    for glyph_image in glyph_images {
+        let min = Vec2::new(glyph_image.offset_x, glyph_image.offset_y);
+        let max = min + glyph_image.size;
+        let z = glyph_image.offset_z;
+        let uv_min = glyph_image.rect.min / page_size;
+        let uv_max = glyph_image.rect.max / page_size;
+
+        let start = positions.len() as u32;
+        positions.extend([
+            [min.x, min.y, z],
+            [max.x, min.y, z],
+            [max.x, max.y, z],
+            [min.x, max.y, z],
+        ]);
+        uvs.extend([
+            [uv_min.x, uv_max.y],
+            [uv_max.x, uv_max.y],
+            [uv_max.x, uv_min.y],
+            [uv_min.x, uv_min.y],
+        ]);
+        fill_uvs.extend(match mapping {
+            TextFillMapping::Block => [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)]
+                .map(|corner| {
+                    let uv = (corner - block.min) / block_size;
+                    [uv.x, 1.0 - uv.y]
+                }),
+            TextFillMapping::Glyph => [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]],
+        });
+        colors.extend([glyph_image.color.to_f32_array(); 4]);
+        indices.extend([start, start + 1, start + 2, start, start + 2, start + 3]);
+    }
+
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_1, fill_uvs)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+pub struct OutlinedTextPlugin;
+
+impl Plugin for OutlinedTextPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(app, TEXT_SHADER_HANDLE, "text.wgsl", Shader::from_wgsl);
+        load_internal_asset!(
+            app,
+            OUTLINED_TEXT_FILL_SHADER_HANDLE,
+            "fill.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            OUTLINED_TEXT_SDF_SHADER_HANDLE,
+            "sdf.wgsl",
+            Shader::from_wgsl
+        );
+
+        app.insert_resource(OutlinedTextImages::default())
+            .init_resource::<GlyphAtlas>()
+            .init_asset::<OutlinedFont>()
+            .init_asset_loader::<OutlinedFontLoader>()
+            .add_plugins(Material2dPlugin::<OutlinedTextSdfMaterial>::default())
+            .add_plugins(Material2dPlugin::<OutlinedTextFillMaterial>::default())
+            .add_systems(Update, edit_text_input)
+            .add_systems(
+                PostUpdate,
+                (
+                    create_missing_text,
+                    update_outlined_text_meshes,
+                    update_text_input_visuals,
+                    blink_text_input_carets,
+                )
+                    .chain()
+                    .before(TransformSystem::TransformPropagate)
+                    .before(VisibilitySystems::VisibilityPropagate),
+            );
+
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.add_systems(
+                ExtractSchedule,
+                extract_outlined_text.after(SpriteSystem::ExtractSprites),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::color::palettes::css::{BLUE, GREEN, RED, WHITE};
+    use bevy::render::mesh::VertexAttributeValues;
+    use swash::scale::image::Content;
+
+    fn cluster(advance: f32, whitespace: Whitespace, bidi_class: BidiClass) -> ShapedCluster {
+        ShapedCluster {
+            section: 0,
+            font: 0,
+            level: 0,
+            paragraph_level: 0,
+            bidi_class,
+            glyphs: Vec::new(),
+            range: 0..1,
+            advance,
+            letter_spacing: 0.0,
+            boundary: Boundary::None,
+            whitespace,
+        }
+    }
+
+    /// Unit-width clusters with line break opportunities after whitespace.
+    fn word(text: &str) -> Vec<ShapedCluster> {
+        let mut clusters: Vec<ShapedCluster> = text
+            .chars()
+            .map(|ch| match ch {
+                ' ' => cluster(1.0, Whitespace::Space, BidiClass::WS),
+                '\t' => cluster(4.0, Whitespace::Tab, BidiClass::S),
+                '\n' => cluster(0.0, Whitespace::Newline, BidiClass::B),
+                _ => cluster(1.0, Whitespace::None, BidiClass::L),
+            })
+            .collect();
+
+        for index in 1..clusters.len() {
+            if clusters[index - 1].whitespace != Whitespace::None {
+                clusters[index].boundary = Boundary::Line;
+            }
+        }
+        clusters
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn break_lines_wraps_at_break_opportunities() {
+        assert_eq!(break_lines(&word("aa bb cc"), 5.0), [0..6, 6..8]);
+        assert_eq!(break_lines(&word("aa bb cc"), f32::MAX), [0..8]);
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn break_lines_splits_words_without_opportunities() {
+        assert_eq!(break_lines(&word("abcdefgh"), 3.0), [0..3, 3..6, 6..8]);
+        assert_eq!(break_lines(&word("a"), 0.0), [0..1]);
+    }
+
+    #[test]
+    fn break_lines_lets_spaces_hang() {
+        assert_eq!(break_lines(&word("ab    cd"), 2.0), [0..6, 6..8]);
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn break_lines_ends_lines_at_newlines() {
+        assert_eq!(break_lines(&word("ab\ncd"), 100.0), [0..2, 3..5]);
+        assert_eq!(break_lines(&word("ab\n"), 100.0), [0..2, 3..3]);
+        assert_eq!(break_lines(&[], 100.0), [0..0]);
+    }
+
+    /// Like [`word`], with one glyph per cluster and one unit of letter spacing.
+    fn spaced_word(text: &str) -> Vec<ShapedCluster> {
+        let mut clusters = word(text);
+        for cluster in &mut clusters {
+            cluster.glyphs.push(Glyph {
+                advance: cluster.advance,
+                ..default()
+            });
+        }
+        apply_spacing(&mut clusters, 1.0, 0.0);
+        clusters
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn break_lines_ignores_letter_spacing_at_the_line_end() {
+        let clusters = spaced_word("ab cd");
+
+        assert_eq!(break_lines(&clusters, 9.0), [0..5]);
+        assert_eq!(break_lines(&clusters, 8.0), [0..3, 3..5]);
+    }
+
+    #[test]
+    fn trim_letter_spacing_skips_trailing_whitespace() {
+        let mut clusters = spaced_word("ab cd ");
+        trim_letter_spacing(&mut clusters, &[0..3, 3..6]);
+
+        let advances: Vec<f32> = clusters.iter().map(|cluster| cluster.advance).collect();
+        assert_eq!(advances, [2.0, 1.0, 2.0, 2.0, 1.0, 2.0]);
+        assert_eq!(clusters[4].glyphs[0].advance, 1.0);
+    }
+
+    #[test]
+    fn line_levels_reset_separators_and_trailing_whitespace() {
+        let mut clusters = word("ab \tcd  ");
+        for cluster in &mut clusters {
+            cluster.level = 1;
+        }
+
+        assert_eq!(line_levels(&clusters, 0), [1, 1, 0, 0, 1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn apply_spacing_adds_word_spacing_to_spaces_only() {
+        let mut clusters = word("a b\nc");
+        for cluster in &mut clusters {
+            cluster.glyphs.push(Glyph {
+                advance: cluster.advance,
+                ..default()
+            });
+        }
+        apply_spacing(&mut clusters, 2.0, 3.0);
+
+        let advances: Vec<f32> = clusters.iter().map(|cluster| cluster.advance).collect();
+        assert_eq!(advances, [3.0, 6.0, 3.0, 0.0, 3.0]);
+        let glyph_advances: Vec<f32> = clusters
+            .iter()
+            .map(|cluster| cluster.glyphs[0].advance)
+            .collect();
+        assert_eq!(glyph_advances, advances);
+    }
+
+    fn metric_line(
+        ascent: f32,
+        descent: f32,
+        leading: f32,
+        paragraph_end: bool,
+    ) -> OutlinedGlyphLine {
+        OutlinedGlyphLine {
+            ascent,
+            descent,
+            leading,
+            paragraph_end,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn stack_lines_adds_paragraph_spacing_after_paragraph_ends() {
+        let mut lines = [
+            metric_line(10.0, 4.0, 2.0, false),
+            metric_line(12.0, 5.0, 1.0, true),
+            metric_line(10.0, 4.0, 0.0, false),
+        ];
+        let height = stack_lines(&mut lines, 7.0);
+
+        let baselines: Vec<f32> = lines.iter().map(|line| line.baseline).collect();
+        assert_eq!(baselines, [-10.0, -28.0, -51.0]);
+        assert_eq!(height, 55.0);
+    }
+
+    fn glyph_line(baseline: f32, glyphs: &[(f32, f32)]) -> OutlinedGlyphLine {
+        OutlinedGlyphLine {
+            glyphs: glyphs
+                .iter()
+                .map(|&(offset_y, height)| GlyphImage {
+                    key: GlyphAtlasKey::new(CacheKey::new(), 0, 16.0, 0, 0.0, 0),
+                    section: 0,
+                    offset_x: 0.0,
+                    offset_y,
+                    offset_z: 0.0,
+                    size: Vec2::new(10.0, height),
+                    color: LinearRgba::WHITE,
+                    fill: None,
+                    image: Handle::default(),
+                    rect: Rect::new(0.0, 0.0, 10.0, height),
+                })
+                .collect(),
+            baseline,
+            ascent: 15.0,
+            descent: 5.0,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn clip_lines_cuts_glyphs_at_the_bottom_edge() {
+        let mut atlas = GlyphAtlas::default();
+        let mut lines = vec![
+            glyph_line(-15.0, &[(-5.0, 20.0)]),
+            glyph_line(-35.0, &[(-5.0, 20.0)]),
+            glyph_line(-55.0, &[(-5.0, 20.0)]),
+        ];
+
+        clip_lines(&mut atlas, &mut lines, -30.0);
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].glyphs[0].size.y, 20.0);
+        let clipped = &lines[1].glyphs[0];
+        assert_eq!(clipped.offset_y, 5.0);
+        assert_eq!(clipped.size.y, 10.0);
+        assert_eq!(clipped.rect, Rect::new(0.0, 0.0, 10.0, 10.0));
+    }
+
+    fn glyph_row(rtl: bool, glyphs: &[f32]) -> OutlinedGlyphLine {
+        let mut line = glyph_line(0.0, &[(0.0, 10.0)]);
+        let template = line.glyphs.pop().unwrap();
+        line.glyphs = glyphs
+            .iter()
+            .map(|&offset_x| GlyphImage {
+                offset_x,
+                ..template.clone()
+            })
+            .collect();
+        line.width = glyphs.len() as f32 * 10.0;
+        line.rtl = rtl;
+        line
+    }
+
+    #[test]
+    fn clip_line_cuts_ltr_lines_at_the_right_edge() {
+        let mut atlas = GlyphAtlas::default();
+        let mut line = glyph_row(false, &[0.0, 10.0, 20.0]);
+
+        clip_line(&mut atlas, &mut line, 15.0);
+
+        assert_eq!(line.width, 15.0);
+        assert_eq!(line.glyphs.len(), 2);
+        assert_eq!(line.glyphs[1].offset_x, 10.0);
+        assert_eq!(line.glyphs[1].size.x, 5.0);
+        assert_eq!(line.glyphs[1].rect, Rect::new(0.0, 0.0, 5.0, 10.0));
+    }
+
+    #[test]
+    fn clip_line_cuts_rtl_lines_at_the_left_edge() {
+        let mut atlas = GlyphAtlas::default();
+        let mut line = glyph_row(true, &[0.0, 10.0, 20.0]);
+
+        clip_line(&mut atlas, &mut line, 15.0);
+
+        assert_eq!(line.width, 15.0);
+        assert_eq!(line.glyphs.len(), 2);
+        let clipped = &line.glyphs[0];
+        assert_eq!(clipped.offset_x, 0.0);
+        assert_eq!(clipped.size.x, 5.0);
+        assert_eq!(clipped.rect, Rect::new(5.0, 0.0, 10.0, 10.0));
+        assert_eq!(line.glyphs[1].offset_x, 5.0);
+        assert_eq!(line.glyphs[1].size.x, 10.0);
+    }
+
+    fn alphas(line: &OutlinedGlyphLine) -> Vec<f32> {
+        line.glyphs.iter().map(|glyph| glyph.color.alpha).collect()
+    }
+
+    #[test]
+    fn fade_line_fades_towards_the_logical_end() {
+        let mut atlas = GlyphAtlas::default();
+        let mut images = Assets::default();
+
+        let mut ltr = glyph_row(false, &[0.0, 10.0, 20.0]);
+        fade_line(&mut atlas, &mut images, &mut ltr, 30.0, 20.0);
+        assert_eq!(alphas(&ltr), [1.0, 0.75, 0.25]);
+
+        let mut rtl = glyph_row(true, &[0.0, 10.0, 20.0]);
+        fade_line(&mut atlas, &mut images, &mut rtl, 30.0, 20.0);
+        assert_eq!(alphas(&rtl), [0.25, 0.75, 1.0]);
+    }
+
+    #[test]
+    fn fading_splits_stretched_decorations_into_steps() {
+        let mut atlas = GlyphAtlas::default();
+        let mut images = Assets::default();
+        let mut line = glyph_row(false, &[0.0, 10.0, 20.0]);
+        let strip = &mut line.glyphs[0];
+        strip.key = GlyphAtlasKey::decoration(CacheKey::new(), 0, 1.0, 0.0);
+        strip.size.x = 30.0;
+        strip.rect = Rect::new(0.0, 0.0, 2.0, 10.0);
+        let background = GlyphImage {
+            key: GlyphAtlasKey::background(0.0),
+            ..line.glyphs[0].clone()
+        };
+        line.glyphs = vec![line.glyphs[0].clone(), background];
+
+        fade_line(&mut atlas, &mut images, &mut line, 30.0, 10.0);
+
+        let strip: Vec<&GlyphImage> = line
+            .glyphs
+            .iter()
+            .filter(|glyph| glyph.key.kind == GlyphAtlasKind::Decoration)
+            .collect();
+        let spans: Vec<(f32, f32)> = strip
+            .iter()
+            .map(|glyph| (glyph.offset_x, glyph.size.x))
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                (0.0, 20.0),
+                (20.0, 2.0),
+                (22.0, 2.0),
+                (24.0, 2.0),
+                (26.0, 2.0),
+                (28.0, 2.0)
+            ]
+        );
+        let strip_alphas: Vec<f32> = strip.iter().map(|glyph| glyph.color.alpha).collect();
+        assert_offsets(&strip_alphas, &[1.0, 0.9, 0.7, 0.5, 0.3, 0.1]);
+        assert!(strip.iter().all(|glyph| glyph.rect.width() == 2.0));
+
+        let background = line
+            .glyphs
+            .iter()
+            .find(|glyph| glyph.key.kind == GlyphAtlasKind::Background)
+            .unwrap();
+        assert_eq!(background.color.alpha, 1.0);
+    }
+
+    #[test]
+    fn fade_lines_fades_towards_the_bottom_edge() {
+        let mut lines = vec![
+            glyph_line(-15.0, &[(-5.0, 20.0)]),
+            glyph_line(-35.0, &[(-5.0, 20.0)]),
+        ];
+
+        let underline = GlyphImage {
+            key: GlyphAtlasKey::decoration(CacheKey::new(), 0, 1.0, 0.0),
+            ..lines[1].glyphs[0].clone()
+        };
+        lines[1].glyphs.push(underline);
+
+        fade_lines(&mut lines, -40.0, 20.0);
+
+        assert_eq!(lines[0].glyphs[0].color.alpha, 1.0);
+        assert_eq!(alphas(&lines[1]), [0.5, 0.5]);
+    }
+
+    #[test]
+    fn fade_lines_reach_gradient_fill_meshes() {
+        let mut lines = vec![glyph_line(-35.0, &[(-5.0, 20.0)])];
+        let fill = TextFill::LinearGradient {
+            from: Color::WHITE,
+            to: Color::BLACK,
+            angle: 0.0,
+            mapping: TextFillMapping::Block,
+        };
+        lines[0].glyphs[0].fill = Some(fill.clone());
+
+        fade_lines(&mut lines, -40.0, 20.0);
+
+        let glyph_images: Vec<&GlyphImage> = lines[0].glyphs.iter().collect();
+        let mesh = glyph_quads_mesh(
+            &glyph_images,
+            Vec2::splat(64.0),
+            fill.mapping(),
+            Rect::new(0.0, 0.0, 10.0, 20.0),
+        );
+        let Some(VertexAttributeValues::Float32x4(colors)) = mesh.attribute(Mesh::ATTRIBUTE_COLOR)
+        else {
+            panic!("glyph quads have no vertex colors");
+        };
+        assert_eq!(colors.len(), 4);
+        assert!(colors.iter().all(|color| color[3] == 0.5));
+    }
+
+    fn itemized(value: &str) -> Vec<(Script, u8, Range<usize>)> {
+        let tokens: Vec<Token> = value
+            .chars()
+            .enumerate()
+            .map(|(index, ch)| Token {
+                ch,
+                offset: index as u32,
+                len: ch.len_utf8() as u8,
+                info: CharInfo::default(),
+                data: index as u32,
+            })
+            .collect();
+        let (levels, _) = bidi::resolve_levels(value, OutlinedTextDirection::Auto);
+
+        itemize_runs(
+            &tokens,
+            &levels,
+            &vec![0; tokens.len()],
+            &[OutlinedTextSection::default()],
+        )
+    }
+
+    #[test]
+    fn itemize_runs_split_at_script_and_level_changes() {
+        assert_eq!(
+            itemized("Hello Мир مرحبا"),
+            [
+                (Script::Latin, 0, 0..6),
+                (Script::Cyrillic, 0, 6..10),
+                (Script::Arabic, 1, 10..15),
+            ]
+        );
+    }
+
+    #[test]
+    fn itemize_runs_let_common_and_inherited_characters_follow_the_script() {
+        // The leading quote takes the first real script, the combining acute
+        // and punctuation stay with the run they follow.
+        assert_eq!(
+            itemized("\u{201c}He\u{301}llo, Мир! مرحبا!"),
+            [
+                (Script::Latin, 0, 0..9),
+                (Script::Cyrillic, 0, 9..14),
+                (Script::Arabic, 1, 14..19),
+                (Script::Arabic, 0, 19..20),
+            ]
+        );
+    }
+
+    #[test]
+    fn variation_coords_snap_to_steps() {
+        assert_eq!(quantize_coord(0), 0);
+        assert_eq!(quantize_coord(127), 0);
+        assert_eq!(quantize_coord(128), 256);
+        assert_eq!(quantize_coord(-300), -256);
+        assert_eq!(quantize_coord(16384), 16384);
+        assert_eq!(quantize_coord(-16384), -16384);
+    }
+
+    fn test_font(fonts: &mut Assets<OutlinedFont>) -> Handle<OutlinedFont> {
+        add_font(fonts, TEST_FONT.to_vec())
+    }
+
+    const TEST_FONT: &[u8] = include_bytes!("../assets/fonts/Montserrat-Regular.ttf");
+
+    /// Hides the `tag` table by lowering the last byte of its tag, which keeps
+    /// the table directory sorted.
+    fn hide_table(mut data: Vec<u8>, tag: &[u8; 4]) -> Vec<u8> {
+        let table_count = u16::from_be_bytes([data[4], data[5]]) as usize;
+        for record in (0..table_count).map(|index| 12 + index * 16) {
+            if &data[record..record + 4] == tag {
+                data[record + 3] -= 1;
+            }
+        }
+        data
+    }
+
+    /// The test font without a `cmap` table, so it maps no characters.
+    fn unmapped_font(fonts: &mut Assets<OutlinedFont>) -> Handle<OutlinedFont> {
+        add_font(fonts, hide_table(TEST_FONT.to_vec(), b"cmap"))
+    }
+
+    /// Packs `faces` into a font collection, moving each face's table offsets
+    /// to where the face lands in the file.
+    fn font_collection(faces: &[&[u8]]) -> Vec<u8> {
+        let mut data = b"ttcf\0\x01\0\0".to_vec();
+        data.extend_from_slice(&(faces.len() as u32).to_be_bytes());
+        let header_len = data.len() + faces.len() * 4;
+
+        let mut face_data = Vec::new();
+        for face in faces {
+            let base = (header_len + face_data.len()) as u32;
+            data.extend_from_slice(&base.to_be_bytes());
+
+            let mut face = face.to_vec();
+            let table_count = u16::from_be_bytes([face[4], face[5]]) as usize;
+            for record in (0..table_count).map(|index| 12 + index * 16) {
+                let offset = &mut face[record + 8..record + 12];
+                let moved = u32::from_be_bytes(offset.try_into().unwrap()) + base;
+                offset.copy_from_slice(&moved.to_be_bytes());
+            }
+            face_data.extend(face);
+            face_data.resize(face_data.len().next_multiple_of(4), 0);
+        }
+
+        data.extend(face_data);
+        data
+    }
+
+    fn loaded_face(data: &[u8], face: OutlinedFontFace) -> (Option<u32>, Vec<(String, u32)>) {
+        let mut labeled = Vec::new();
+        let selected = load_faces(Arc::new(data.to_vec()), &face, |label, font| {
+            labeled.push((label, font.offset));
+        });
+        (selected.ok().map(|font| font.offset), labeled)
+    }
+
+    #[test]
+    fn font_loader_selects_faces_by_index_and_name() {
+        let bold = include_bytes!("../assets/fonts/Montserrat-Bold.ttf");
+        let collection = font_collection(&[TEST_FONT, bold]);
+        let (_, labeled) = loaded_face(&collection, OutlinedFontFace::Index(0));
+        let labels: Vec<&str> = labeled.iter().map(|(label, _)| label.as_str()).collect();
+        assert_eq!(labels, ["face0", "face1"]);
+        let (regular, bold) = (labeled[0].1, labeled[1].1);
+
+        let select = |face| loaded_face(&collection, face).0;
+        assert_eq!(select(OutlinedFontFace::Index(1)), Some(bold));
+        assert_eq!(select(OutlinedFontFace::Index(2)), None);
+        assert_eq!(
+            select(OutlinedFontFace::Name("Montserrat-Bold".into())),
+            Some(bold)
+        );
+        assert_eq!(
+            select(OutlinedFontFace::Name("Montserrat Bold".into())),
+            Some(bold)
+        );
+        assert_eq!(
+            select(OutlinedFontFace::Name("Montserrat".into())),
+            Some(regular)
+        );
+        assert_eq!(select(OutlinedFontFace::Name("Roboto".into())), None);
+
+        let single = loaded_face(TEST_FONT, OutlinedFontFace::Index(0));
+        assert_eq!(single, (Some(0), Vec::new()));
+        assert!(matches!(
+            load_faces(Arc::new(b"not a font".to_vec()), &default(), |_, _| {}),
+            Err(OutlineFontLoaderError::InvalidFont)
+        ));
+    }
+
+    fn add_font(fonts: &mut Assets<OutlinedFont>, data: Vec<u8>) -> Handle<OutlinedFont> {
+        let font_ref = FontRef::from_index(&data, 0).unwrap();
+        let (offset, key) = (font_ref.offset, font_ref.key);
+
+        fonts.add(OutlinedFont {
+            data: Arc::new(data),
+            offset,
+            key,
+        })
+    }
+
+    fn test_text(font: Handle<OutlinedFont>, value: &str) -> OutlinedText {
+        OutlinedText {
+            sections: vec![OutlinedTextSection {
+                value: value.to_string(),
+                ..default()
+            }],
+            font_style: OutlinedFontStyle {
+                font,
+                size: 20.0,
+                auto_size: Some(AutoSize {
+                    min_size: 8.0,
+                    max_size: 200.0,
+                }),
+                ..default()
+            },
+            ..default()
+        }
+    }
+
+    fn measure(fonts: &Assets<OutlinedFont>, text: &OutlinedText, size: f32, width: f32) -> Vec2 {
+        let section_fonts = text_section_fonts(fonts, text, size, 1.0).unwrap();
+        measure_text(&mut ShapeContext::new(), &section_fonts, text, width, 1.0).unwrap()
+    }
+
+    #[test]
+    fn unmapped_clusters_fall_back_to_the_next_font() {
+        let mut fonts = Assets::default();
+        let font = test_font(&mut fonts);
+        let unmapped = unmapped_font(&mut fonts);
+
+        let mut text = test_text(unmapped.clone(), "Hi");
+        text.font_style.fallbacks = vec![unmapped.clone(), font.clone()];
+        let clusters = shaped(&fonts, &text);
+        assert!(clusters.iter().all(|cluster| cluster.font == 2));
+
+        let mut text = test_text(font, "Hi");
+        text.font_style.fallbacks = vec![unmapped.clone()];
+        let clusters = shaped(&fonts, &text);
+        assert!(clusters.iter().all(|cluster| cluster.font == 0));
+
+        // Nothing maps the cluster, so it stays with the primary font.
+        let mut text = test_text(unmapped.clone(), "Hi");
+        text.font_style.fallbacks = vec![unmapped];
+        let clusters = shaped(&fonts, &text);
+        assert!(clusters.iter().all(|cluster| cluster.font == 0));
+    }
+
+    #[test]
+    fn missing_fallbacks_do_not_block_the_primary_font() {
+        let mut fonts = Assets::default();
+        let font = test_font(&mut fonts);
+        let loading = Handle::<OutlinedFont>::weak_from_u128(1);
+
+        let mut text = test_text(font.clone(), "Hi");
+        text.font_style.fallbacks = vec![loading.clone(), font];
+        let section_fonts = text_section_fonts(&fonts, &text, 20.0, 1.0).unwrap();
+        assert_eq!(section_fonts[0].len(), 2);
+
+        let text = test_text(loading, "Hi");
+        assert!(text_section_fonts(&fonts, &text, 20.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn fit_font_size_finds_the_largest_fitting_size() {
+        let mut fonts = Assets::default();
+        let text = test_text(test_font(&mut fonts), "Hello outlined world");
+        let bounds = OutlinedTextBounds {
+            width: 300.0,
+            height: 100.0,
+        };
+        let auto_size = text.font_style.auto_size.unwrap();
+        let mut shape_context = ShapeContext::new();
+
+        let size = fit_font_size(
+            &mut shape_context,
+            &fonts,
+            &text,
+            Some(&bounds),
+            auto_size,
+            1.0,
+        )
+        .unwrap();
+        assert!(size > auto_size.min_size && size < auto_size.max_size);
+
+        let fitted = measure(&fonts, &text, size, bounds.width);
+        assert!(fitted.x <= bounds.width && fitted.y <= bounds.height);
+
+        let step = (auto_size.max_size - auto_size.min_size) / (1 << AUTO_SIZE_STEPS) as f32;
+        let larger = measure(&fonts, &text, size + step, bounds.width);
+        assert!(larger.x > bounds.width || larger.y > bounds.height);
+    }
+
+    #[test]
+    fn fit_font_size_clamps_to_the_limits() {
+        let mut fonts = Assets::default();
+        let text = test_text(test_font(&mut fonts), "Hi");
+        let auto_size = text.font_style.auto_size.unwrap();
+        let mut shape_context = ShapeContext::new();
+
+        let roomy = OutlinedTextBounds {
+            width: 10000.0,
+            height: 10000.0,
+        };
+        let fitted = fit_font_size(
+            &mut shape_context,
+            &fonts,
+            &text,
+            Some(&roomy),
+            auto_size,
+            1.0,
+        );
+        assert_eq!(fitted, Some(auto_size.max_size));
+
+        let tiny = OutlinedTextBounds {
+            width: 1.0,
+            height: 1.0,
+        };
+        let fitted = fit_font_size(
+            &mut shape_context,
+            &fonts,
+            &text,
+            Some(&tiny),
+            auto_size,
+            1.0,
+        );
+        assert_eq!(fitted, Some(auto_size.min_size));
+    }
+
+    #[test]
+    fn explicit_section_sizes_scale_with_the_fitted_size() {
+        let mut fonts = Assets::default();
+        let mut text = test_text(test_font(&mut fonts), "a");
+        text.sections.push(OutlinedTextSection {
+            value: "b".to_string(),
+            size: Some(40.0),
+            ..default()
+        });
+
+        let section_fonts = text_section_fonts(&fonts, &text, 10.0, 1.0).unwrap();
+        assert_eq!(section_fonts[0][0].size, 10.0);
+        assert_eq!(section_fonts[1][0].size, 20.0);
+    }
+
+    #[test]
+    fn spacing_widens_measured_lines() {
+        let mut fonts = Assets::default();
+        let mut text = test_text(test_font(&mut fonts), "Hi all");
+        let plain = measure(&fonts, &text, 20.0, f32::MAX);
+
+        text.font_style.letter_spacing = 2.0;
+        let letter_spaced = measure(&fonts, &text, 20.0, f32::MAX);
+        assert!((letter_spaced.x - plain.x - 5.0 * 2.0).abs() < 1e-3);
+
+        text.font_style.word_spacing = 3.0;
+        let word_spaced = measure(&fonts, &text, 20.0, f32::MAX);
+        assert!((word_spaced.x - letter_spaced.x - 3.0).abs() < 1e-3);
+        assert_eq!(word_spaced.y, plain.y);
+    }
+
+    #[test]
+    fn line_height_sets_the_distance_between_baselines() {
+        let mut fonts = Assets::default();
+        let mut text = test_text(test_font(&mut fonts), "Hi\nall");
+
+        let mut height = |line_height, paragraph_spacing| {
+            text.font_style.line_height = line_height;
+            text.font_style.paragraph_spacing = paragraph_spacing;
+            measure(&fonts, &text, 20.0, f32::MAX).y
+        };
+
+        assert!((height(LineHeight::Absolute(30.0), 0.0) - 60.0).abs() < 1e-3);
+        assert!((height(LineHeight::Multiplier(2.0), 0.0) - 80.0).abs() < 1e-3);
+        assert!((height(LineHeight::Multiplier(2.0), 5.0) - 85.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn line_height_centers_the_font_in_the_line() {
+        let mut fonts = Assets::default();
+        let text = test_text(test_font(&mut fonts), "Hi");
+        let section_fonts = text_section_fonts(&fonts, &text, 20.0, 2.0).unwrap();
+        let clusters = shaped(&fonts, &text);
+        let metrics = section_fonts[0][0].metrics;
+
+        let mut line = OutlinedGlyphLine::default();
+        set_line_metrics(
+            &mut line,
+            &clusters,
+            &section_fonts,
+            LineHeight::Absolute(60.0),
+            2.0,
+        );
+        assert!((line.ascent + line.descent - 30.0).abs() < 1e-3);
+        assert!(((line.ascent - metrics.ascent) - (line.descent - metrics.descent)).abs() < 1e-3);
+        assert_eq!(line.leading, 0.0);
+    }
+
+    fn text_app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<WindowScaleFactorChanged>()
+            .add_event::<AssetEvent<OutlinedFont>>()
+            .init_resource::<Assets<Image>>()
+            .init_resource::<Assets<OutlinedFont>>()
+            .init_resource::<GlyphAtlas>()
+            .init_resource::<OutlinedTextImages>()
+            .add_systems(Update, create_missing_text);
+        app
+    }
+
+    fn laid_out(
+        text: &str,
+        justify: JustifyOutlinedText,
+        direction: OutlinedTextDirection,
+        width: Option<f32>,
+    ) -> OutlinedTextLayout {
+        let mut app = text_app();
+        let font = test_font(&mut app.world_mut().resource_mut::<Assets<OutlinedFont>>());
+        let mut text = test_text(font, text);
+        text.font_style.auto_size = None;
+        text.justify = justify;
+        text.direction = direction;
+
+        let mut entity = app.world_mut().spawn((text, Anchor::default()));
+        if let Some(width) = width {
+            entity.insert(OutlinedTextBounds {
+                width,
+                height: 1000.0,
+            });
+        }
+        let entity = entity.id();
+
+        app.update();
+        app.world()
+            .get::<OutlinedTextLayout>(entity)
+            .unwrap()
+            .clone()
+    }
+
+    /// Glyph positions relative to the start of their line.
+    fn line_offsets(layout: &OutlinedTextLayout, line: usize) -> Vec<f32> {
+        let line = &layout.lines[line];
+        layout.glyphs[line.glyphs.clone()]
+            .iter()
+            .map(|glyph| glyph.position.x - line.left)
+            .collect()
+    }
+
+    fn assert_offsets(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < 1e-3,
+                "{actual:?} != {expected:?}"
+            );
+        }
+    }
+
+    /// Lays out "aa bb cc dd" justified to 10 units more than "aa bb cc",
+    /// returning the natural glyph offsets alongside the justified layout.
+    fn justified(
+        inter_character: bool,
+        direction: OutlinedTextDirection,
+    ) -> (Vec<f32>, OutlinedTextLayout) {
+        let text = "aa bb cc dd";
+        let natural = laid_out(text, JustifyOutlinedText::Left, direction, None);
+        let natural_offsets = line_offsets(&natural, 0);
+        let cc = &natural.glyphs[7];
+        let width = cc.position.x + cc.advance - natural.lines[0].left + 10.0;
+
+        let layout = laid_out(
+            text,
+            JustifyOutlinedText::Justified { inter_character },
+            direction,
+            Some(width),
+        );
+        assert_eq!(layout.lines.len(), 2);
+        (natural_offsets, layout)
+    }
+
+    #[test]
+    fn justification_spreads_words_to_the_width() {
+        let (natural, layout) = justified(false, OutlinedTextDirection::LeftToRight);
+
+        let expected: Vec<f32> = natural[..8]
+            .iter()
+            .enumerate()
+            .map(|(index, offset)| offset + (index / 3) as f32 * 5.0)
+            .collect();
+        assert_offsets(&line_offsets(&layout, 0), &expected);
+        assert!((layout.lines[0].left + layout.lines[0].width - layout.rect.max.x).abs() < 1e-3);
+    }
+
+    #[test]
+    fn inter_character_justification_spreads_every_cluster() {
+        let (natural, layout) = justified(true, OutlinedTextDirection::LeftToRight);
+
+        let expected: Vec<f32> = natural[..8]
+            .iter()
+            .enumerate()
+            .map(|(index, offset)| offset + index as f32 * 10.0 / 7.0)
+            .collect();
+        assert_offsets(&line_offsets(&layout, 0), &expected);
+    }
+
+    #[test]
+    fn justification_leaves_the_last_line_alone() {
+        let (natural, layout) = justified(false, OutlinedTextDirection::LeftToRight);
+
+        let last = line_offsets(&layout, 1);
+        let dd: Vec<f32> = natural[9..]
+            .iter()
+            .map(|offset| offset - natural[9])
+            .collect();
+        assert_offsets(&last, &dd);
+        assert_eq!(layout.lines[1].left, layout.lines[0].left);
+        assert!(layout.lines[1].width < layout.lines[0].width);
+    }
+
+    #[test]
+    fn justified_rtl_lines_align_right() {
+        let (_, layout) = justified(false, OutlinedTextDirection::RightToLeft);
+
+        let right = |line: &OutlinedTextLayoutLine| line.left + line.width;
+        assert!((right(&layout.lines[0]) - layout.rect.max.x).abs() < 1e-3);
+        assert!((right(&layout.lines[1]) - layout.rect.max.x).abs() < 1e-3);
+        assert!(layout.lines[1].left > layout.lines[0].left);
+    }
+
+    #[test]
+    fn fitted_size_is_cached_until_the_layout_changes() {
+        let mut app = text_app();
+
+        let font = test_font(&mut app.world_mut().resource_mut::<Assets<OutlinedFont>>());
+        let entity = app
+            .world_mut()
+            .spawn((
+                test_text(font, "Hello outlined world"),
+                Anchor::default(),
+                OutlinedTextBounds {
+                    width: 300.0,
+                    height: 100.0,
+                },
+            ))
+            .id();
+
+        app.update();
+        let fitted = app
+            .world()
+            .get::<OutlinedTextFittedSize>(entity)
+            .unwrap()
+            .size;
+        let layout_width = app
+            .world()
+            .get::<OutlinedTextLayout>(entity)
+            .unwrap()
+            .size
+            .x;
+
+        // Moving the anchor reuses the cached size instead of searching again.
+        app.world_mut().entity_mut(entity).insert((
+            OutlinedTextFittedSize { size: fitted / 2.0 },
+            Anchor::TopLeft,
+        ));
+        app.update();
+        let size = app
+            .world()
+            .get::<OutlinedTextFittedSize>(entity)
+            .unwrap()
+            .size;
+        let layout = app.world().get::<OutlinedTextLayout>(entity).unwrap();
+        assert_eq!(size, fitted / 2.0);
+        assert!(layout.size.x < layout_width * 0.75);
+
+        // Changing the bounds fits the text again.
+        app.world_mut()
+            .entity_mut(entity)
+            .insert(OutlinedTextBounds {
+                width: 150.0,
+                height: 100.0,
+            });
+        app.update();
+        let size = app
+            .world()
+            .get::<OutlinedTextFittedSize>(entity)
+            .unwrap()
+            .size;
+        assert!(size < fitted && size != fitted / 2.0);
+    }
+
+    #[test]
+    fn cached_glyphs_keep_the_fitted_section_sizes() {
+        let mut app = text_app();
+
+        let font = test_font(&mut app.world_mut().resource_mut::<Assets<OutlinedFont>>());
+        let mut text = test_text(font, "Hello ");
+        text.sections.push(OutlinedTextSection {
+            value: "world".to_string(),
+            size: Some(40.0),
+            ..default()
+        });
+        let entity = app
+            .world_mut()
+            .spawn((
+                text,
+                Anchor::default(),
+                OutlinedTextBounds {
+                    width: 300.0,
+                    height: 100.0,
+                },
+            ))
+            .id();
+
+        app.update();
+        let fitted = app
+            .world()
+            .get::<OutlinedTextFittedSize>(entity)
+            .unwrap()
+            .size;
+        let images = app.world().resource::<OutlinedTextImages>();
+        assert_eq!(images.cache[&entity].section_sizes, [fitted, fitted * 2.0]);
+    }
+
+    fn shadow_pixels(images: &Assets<Image>, glyph_image: &GlyphImage) -> Vec<u8> {
+        let page = images.get(&glyph_image.image).unwrap();
+        let rect = glyph_image.rect;
+        (rect.min.y as usize..rect.max.y as usize)
+            .flat_map(|y| {
+                (rect.min.x as usize..rect.max.x as usize)
+                    .map(move |x| page.data[(y * page.width() as usize + x) * 4 + 3])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn overlapping_glyphs_share_one_shadow() {
+        let mut fonts = Assets::default();
+        let mut text = test_text(test_font(&mut fonts), "A");
+        text.sections[0].shadow = Some(ShadowStyle {
+            offset: Vec2::new(2.0, -2.0),
+            blur_radius: 3.0,
+            color: Color::BLACK,
+        });
+        let section_fonts = text_section_fonts(&fonts, &text, 20.0, 1.0).unwrap();
+        let glyph_id = section_fonts[0][0].font_ref.charmap().map('A');
+        let glyph = |x| ShadowGlyph {
+            section: 0,
+            font: 0,
+            id: glyph_id,
+            x,
+            y: 0,
+            subpixel_offset: 0,
+            color: false,
+        };
+
+        let mut glyph_atlas = GlyphAtlas::default();
+        let mut images = Assets::default();
+        let mut scale_context = ScaleContext::new();
+        let mut shadow = |run: &[ShadowGlyph]| {
+            shadow_image(
+                &mut glyph_atlas,
+                &mut images,
+                &mut scale_context,
+                &section_fonts[0],
+                0,
+                &text.sections[0],
+                run,
+                1.0,
+            )
+            .unwrap()
+        };
+
+        let single = shadow(&[glyph(10)]);
+        let stacked = shadow(&[glyph(10), glyph(10)]);
+        let shifted = shadow(&[glyph(30)]);
+        let pair = shadow(&[glyph(10), glyph(14)]);
+
+        assert_eq!(shifted.key, single.key);
+        assert_ne!(stacked.key, single.key);
+        assert_eq!(shifted.offset_x - single.offset_x, 20.0);
+        assert_eq!(pair.size.x, single.size.x + 4.0);
+        assert_eq!(glyph_atlas.statistics().live_glyphs, 3);
+
+        glyph_atlas.write_pending(&mut images);
+        assert_eq!(
+            shadow_pixels(&images, &stacked),
+            shadow_pixels(&images, &single)
+        );
+    }
+
+    #[test]
+    fn glyphs_without_outlines_render_empty() {
+        let data = hide_table(TEST_FONT.to_vec(), b"glyf");
+        let font = FontInstance::new(FontRef::from_index(&data, 0).unwrap(), &[], 20.0);
+        let glyph_id = font.font_ref.charmap().map('A');
+        let outline = OutlineLayer::new(2.0, Color::BLACK);
+        let mut scale_context = ScaleContext::new();
+        let mut scaler = build_scaler(&mut scale_context, &font, font.size, true);
+        let offset = Vector::new(0.0, 0.0);
+
+        for bitmap in [
+            glyph_to_bitmap(glyph_id, offset, &mut scaler),
+            glyph_silhouette(glyph_id, false, offset, &mut scaler),
+            outline_to_bitmap(glyph_id, &outline, 2.0, false, offset, &mut scaler),
+            outline_to_bitmap(glyph_id, &outline, 2.0, true, offset, &mut scaler),
+        ] {
+            assert!(bitmap.data.is_empty());
+        }
+    }
+
+    #[test]
+    fn outlined_and_shadowed_text_without_outlines_lays_out() {
+        let mut app = text_app();
+        let font = add_font(
+            &mut app.world_mut().resource_mut::<Assets<OutlinedFont>>(),
+            hide_table(TEST_FONT.to_vec(), b"glyf"),
+        );
+        let mut text = test_text(font, "A B");
+        text.font_style.auto_size = None;
+        text.sections[0].outline =
+            OutlineStyle::Stacked(vec![OutlineLayer::new(2.0, Color::BLACK)]);
+        text.sections[0].shadow = Some(ShadowStyle {
+            offset: Vec2::new(2.0, -2.0),
+            blur_radius: 3.0,
+            color: Color::BLACK,
+        });
+        let entity = app.world_mut().spawn((text, Anchor::default())).id();
+
+        app.update();
+        assert!(app.world().get::<OutlinedTextLayout>(entity).is_some());
+    }
+
+    /// Adds `tables` to the font, keeping the table directory sorted.
+    fn add_tables(data: &[u8], tables: &mut [([u8; 4], Vec<u8>)]) -> Vec<u8> {
+        let table_count = u16::from_be_bytes([data[4], data[5]]) as usize;
+        let directory_end = 12 + table_count * 16;
+        let shift = (tables.len() * 16) as u32;
+
+        let mut records: Vec<[u8; 16]> = data[12..directory_end]
+            .chunks_exact(16)
+            .map(|record| {
+                let mut record: [u8; 16] = record.try_into().unwrap();
+                let offset = u32::from_be_bytes(record[8..12].try_into().unwrap()) + shift;
+                record[8..12].copy_from_slice(&offset.to_be_bytes());
+                record
+            })
+            .collect();
+
+        let mut body = data[directory_end..].to_vec();
+        for (tag, table) in tables.iter_mut() {
+            body.resize(body.len().next_multiple_of(4), 0);
+            let offset = (directory_end as u32 + shift) + body.len() as u32;
+            let mut record = [0; 16];
+            record[..4].copy_from_slice(tag);
+            record[8..12].copy_from_slice(&offset.to_be_bytes());
+            record[12..].copy_from_slice(&(table.len() as u32).to_be_bytes());
+            records.push(record);
+            body.append(table);
+        }
+        records.sort_by_key(|record| <[u8; 4]>::try_from(&record[..4]).unwrap());
+
+        let mut font = data[..12].to_vec();
+        font[4..6].copy_from_slice(&(records.len() as u16).to_be_bytes());
+        font.extend(records.concat());
+        font.extend(body);
+        font
+    }
+
+    /// The test font with a red COLR glyph for `o`, drawn with the `H` outline
+    /// so its silhouette differs from its own outline.
+    fn color_font() -> Vec<u8> {
+        let font_ref = FontRef::from_index(TEST_FONT, 0).unwrap();
+        let charmap = font_ref.charmap();
+        let (base, layer) = (charmap.map('o'), charmap.map('H'));
+
+        let be = |values: &[u16]| -> Vec<u8> {
+            values
+                .iter()
+                .flat_map(|value| value.to_be_bytes())
+                .collect()
+        };
+        // Version 0, one base glyph record at 14 and one layer record at 20.
+        let mut colr = be(&[0, 1, 0, 14, 0, 20, 1]);
+        colr.extend(be(&[base, 0, 1, layer, 0]));
+        // Version 0, one palette with one color record at 14, stored as BGRA.
+        let mut cpal = be(&[0, 1, 1, 1, 0, 14, 0]);
+        cpal.extend([0, 0, 255, 255]);
+
+        add_tables(TEST_FONT, &mut [(*b"COLR", colr), (*b"CPAL", cpal)])
+    }
+
+    #[test]
+    fn stacked_outlines_draw_earlier_layers_on_top() {
+        let mut app = text_app();
+        let font = test_font(&mut app.world_mut().resource_mut::<Assets<OutlinedFont>>());
+        let layer = |width, color: Srgba, placement| OutlineLayer {
+            placement,
+            ..OutlineLayer::new(width, color)
+        };
+        let mut text = test_text(font, "H");
+        text.font_style.auto_size = None;
+        text.sections[0].outline = OutlineStyle::Stacked(vec![
+            layer(4.0, RED, OutlinePlacement::Outside),
+            layer(2.0, BLUE, OutlinePlacement::Center),
+            layer(1.0, GREEN, OutlinePlacement::Inside),
+            layer(2.0, WHITE, OutlinePlacement::Inside),
+        ]);
+        let entity = app.world_mut().spawn((text, Anchor::default())).id();
+        app.update();
+
+        let images = app.world().resource::<OutlinedTextImages>();
+        let glyphs = &images.cache[&entity].glyphs;
+        let fill_z = glyphs
+            .iter()
+            .find(|glyph_image| glyph_image.key.outline_width == 0)
+            .map(|glyph_image| glyph_image.offset_z)
+            .unwrap();
+        let outlines: Vec<(LinearRgba, f32)> = glyphs
+            .iter()
+            .filter(|glyph_image| glyph_image.key.outline_width > 0)
+            .map(|glyph_image| (glyph_image.color, glyph_image.offset_z - fill_z))
+            .collect();
+
+        // Outer layers sit below the fill and inside ones above it, each
+        // layer above the ones listed after it.
+        assert_eq!(
+            outlines,
+            [
+                (RED.into(), -LAYER_DEPTH),
+                (BLUE.into(), -2.0 * LAYER_DEPTH),
+                (GREEN.into(), 2.0 * LAYER_DEPTH),
+                (WHITE.into(), LAYER_DEPTH),
+            ]
+        );
+    }
+
+    fn fill_colors(app: &mut App, text: OutlinedText) -> Vec<LinearRgba> {
+        let entity = app.world_mut().spawn((text, Anchor::default())).id();
+        app.update();
+
+        let images = app.world().resource::<OutlinedTextImages>();
+        images.cache[&entity]
+            .glyphs
+            .iter()
+            .filter(|glyph_image| {
+                matches!(
+                    glyph_image.key.kind,
+                    GlyphAtlasKind::Coverage | GlyphAtlasKind::Color
+                )
+            })
+            .map(|glyph_image| glyph_image.color)
+            .collect()
+    }
+
+    #[test]
+    fn color_glyphs_are_only_tinted_when_asked() {
+        let mut app = text_app();
+        let font = add_font(
+            &mut app.world_mut().resource_mut::<Assets<OutlinedFont>>(),
+            color_font(),
+        );
+        let mut text = test_text(font, "oH");
+        text.font_style.auto_size = None;
+        let blue = Color::srgb(0.0, 0.0, 1.0);
+        text.sections[0].color = TextFill::Solid(blue);
+
+        let blue = blue.to_linear();
+        assert_eq!(
+            fill_colors(&mut app, text.clone()),
+            [LinearRgba::WHITE, blue]
+        );
+
+        text.sections[0].tint_color_glyphs = true;
+        assert_eq!(fill_colors(&mut app, text), [blue, blue]);
+    }
+
+    #[test]
+    fn color_font_outlines_are_cached_as_silhouettes() {
+        let mut app = text_app();
+        let font = add_font(
+            &mut app.world_mut().resource_mut::<Assets<OutlinedFont>>(),
+            color_font(),
+        );
+        let mut text = test_text(font, "oH");
+        text.font_style.auto_size = None;
+        text.sections[0].outline =
+            OutlineStyle::Stacked(vec![OutlineLayer::new(2.0, Color::BLACK)]);
+        let entity = app.world_mut().spawn((text, Anchor::default())).id();
+        app.update();
+
+        let images = app.world().resource::<OutlinedTextImages>();
+        let kinds: Vec<GlyphAtlasKind> = images.cache[&entity]
+            .glyphs
+            .iter()
+            .map(|glyph_image| glyph_image.key.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                GlyphAtlasKind::Silhouette,
+                GlyphAtlasKind::Color,
+                GlyphAtlasKind::Silhouette,
+                GlyphAtlasKind::Color,
+            ]
+        );
+    }
+
+    #[test]
+    fn color_glyph_outlines_surround_the_silhouette() {
+        let data = color_font();
+        let font = FontInstance::new(FontRef::from_index(&data, 0).unwrap(), &[], 20.0);
+        let charmap = font.font_ref.charmap();
+        let (o, h) = (charmap.map('o'), charmap.map('H'));
+        let outline = OutlineLayer {
+            placement: OutlinePlacement::Outside,
+            ..OutlineLayer::new(2.0, Color::BLACK)
+        };
+        let mut scale_context = ScaleContext::new();
+        let mut scaler = build_scaler(&mut scale_context, &font, font.size, true);
+        let offset = Vector::new(0.0, 0.0);
+
+        let color_o = glyph_to_color_bitmap(o, offset, &mut scaler);
+        assert_eq!(color_o.content, Content::Color);
+
+        // The COLR layer draws `o` with the `H` outline, which is taller.
+        let height = |bitmap: SwashImage| (bitmap.placement.top, bitmap.placement.height);
+        let plain_o = height(glyph_to_bitmap(o, offset, &mut scaler));
+        let h_height = height(glyph_to_bitmap(h, offset, &mut scaler));
+        assert_ne!(plain_o, h_height);
+        assert_eq!(
+            height(glyph_silhouette(o, true, offset, &mut scaler)),
+            h_height
+        );
+        assert_eq!(
+            height(outline_to_bitmap(
+                o,
+                &outline,
+                2.0,
+                true,
+                offset,
+                &mut scaler
+            )),
+            height(outline_to_bitmap(
+                h,
+                &outline,
+                2.0,
+                true,
+                offset,
+                &mut scaler
+            ))
+        );
+
+        // The outline surrounds the silhouette without covering it.
+        let silhouette = glyph_silhouette(o, true, offset, &mut scaler);
+        let ring = outline_to_bitmap(o, &outline, 2.0, true, offset, &mut scaler);
+        assert!(ring.placement.width > silhouette.placement.width);
+        assert!(ring.data.contains(&255));
+        let dx = silhouette.placement.left - ring.placement.left;
+        let dy = ring.placement.top - silhouette.placement.top;
+        for y in 0..silhouette.placement.height as i32 {
+            for x in 0..silhouette.placement.width as i32 {
+                let inside = silhouette.data[(y * silhouette.placement.width as i32 + x) as usize];
+                let ring_alpha =
+                    ring.data[((y + dy) * ring.placement.width as i32 + x + dx) as usize];
+                if inside == 255 {
+                    assert_eq!(ring_alpha, 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn color_glyph_shadows_use_the_silhouette() {
+        let mut fonts = Assets::default();
+        let mut text = test_text(add_font(&mut fonts, color_font()), "oH");
+        text.sections[0].shadow = Some(ShadowStyle {
+            offset: Vec2::new(2.0, -2.0),
+            blur_radius: 3.0,
+            color: Color::BLACK,
+        });
+        let section_fonts = text_section_fonts(&fonts, &text, 20.0, 1.0).unwrap();
+        let charmap = section_fonts[0][0].font_ref.charmap();
+        let glyph = |ch, color| ShadowGlyph {
+            section: 0,
+            font: 0,
+            id: charmap.map(ch),
+            x: 10,
+            y: 0,
+            subpixel_offset: 0,
+            color,
+        };
+
+        let mut glyph_atlas = GlyphAtlas::default();
+        let mut images = Assets::default();
+        let mut scale_context = ScaleContext::new();
+        let mut shadow = |run: &[ShadowGlyph]| {
+            shadow_image(
+                &mut glyph_atlas,
+                &mut images,
+                &mut scale_context,
+                &section_fonts[0],
+                0,
+                &text.sections[0],
+                run,
+                1.0,
+            )
+            .unwrap()
+        };
+
+        // The COLR layer draws `o` with the `H` outline, which is taller.
+        let plain_o = shadow(&[glyph('o', false)]);
+        let o = shadow(&[glyph('o', true)]);
+        let h = shadow(&[glyph('H', true)]);
+        assert_eq!(o.size.y, h.size.y);
+        assert_eq!(o.offset_y, h.offset_y);
+        assert_ne!(o.size.y, plain_o.size.y);
+    }
+
+    fn shaped(fonts: &Assets<OutlinedFont>, text: &OutlinedText) -> Vec<ShapedCluster> {
+        let section_fonts = text_section_fonts(fonts, text, 20.0, 1.0).unwrap();
+        shape_text(&mut ShapeContext::new(), &section_fonts, text)
+    }
+
+    #[test]
+    fn feature_settings_let_sections_override_the_style() {
+        let tag = |bytes: &[u8; 4]| tag_from_bytes(bytes);
+        let style = OutlinedFontStyle {
+            features: vec![(tag(b"liga"), 1), (tag(b"kern"), 1), (tag(b"tnum"), 1)],
+            ..default()
+        };
+        let section = OutlinedTextSection {
+            features: vec![(tag(b"tnum"), 0), (tag(b"smcp"), 1), (tag(b"liga"), 0)],
+            ..default()
+        };
+
+        assert_eq!(
+            style.feature_settings(&section),
+            [
+                (tag(b"kern"), 1),
+                (tag(b"tnum"), 0),
+                (tag(b"smcp"), 1),
+                (tag(b"liga"), 0),
+            ]
+        );
+        assert_eq!(style.feature_settings(&default()), style.features);
+    }
+
+    #[test]
+    fn features_change_the_shaped_glyphs() {
+        let mut fonts = Assets::default();
+        let mut text = test_text(test_font(&mut fonts), "fi");
+        let glyph_count = |text: &OutlinedText| -> usize {
+            shaped(&fonts, text)
+                .iter()
+                .map(|cluster| cluster.glyphs.len())
+                .sum()
+        };
+        assert_eq!(glyph_count(&text), 1);
+
+        text.font_style.features = vec![(tag_from_bytes(b"liga"), 0)];
+        assert_eq!(glyph_count(&text), 2);
+
+        text.sections[0].features = vec![(tag_from_bytes(b"liga"), 1)];
+        assert_eq!(glyph_count(&text), 1);
+    }
+
+    fn ellipsized(fonts: &Assets<OutlinedFont>, text: &OutlinedText, max_width: f32) -> String {
+        let section_fonts = text_section_fonts(fonts, text, 20.0, 1.0).unwrap();
+        let clusters = shape_text(&mut ShapeContext::new(), &section_fonts, text);
+        ellipsize(
+            &mut ShapeContext::new(),
+            &section_fonts,
+            text,
+            &clusters,
+            "…",
+            max_width,
+            0,
+            1.0,
+        )
+        .iter()
+        .map(|cluster| {
+            if cluster.range.is_empty() {
+                "…"
+            } else {
+                &text.sections[cluster.section].value[cluster.range.clone()]
+            }
+        })
+        .collect()
+    }
+
+    #[test]
+    fn ellipsize_keeps_what_fits_before_the_ellipsis() {
+        let mut fonts = Assets::default();
+        let text = test_text(test_font(&mut fonts), "Hello world");
+        let clusters = shaped(&fonts, &text);
+        let ellipsis = shaped(&fonts, &test_text(text.font_style.font.clone(), "…"));
+        let ellipsis_width = content_width(&ellipsis);
+
+        let hello = content_width(&clusters[..5]);
+        assert_eq!(ellipsized(&fonts, &text, hello + ellipsis_width), "Hello…");
+        // The space before the cut is trimmed rather than kept before the ellipsis.
+        let space = content_width(&clusters[..6]);
+        assert_eq!(
+            ellipsized(&fonts, &text, space + ellipsis_width + 1.0),
+            "Hello…"
+        );
+        assert_eq!(ellipsized(&fonts, &text, ellipsis_width), "…");
+    }
+
+    #[test]
+    fn ellipsize_uses_the_style_of_the_last_kept_section() {
+        let mut fonts = Assets::default();
+        let mut text = test_text(test_font(&mut fonts), "Hello ");
+        text.sections.push(OutlinedTextSection {
+            value: "world".to_string(),
+            size: Some(40.0),
+            ..default()
+        });
+        let clusters = shaped(&fonts, &text);
+        let mut ellipsis = test_text(text.font_style.font.clone(), "…");
+        ellipsis.sections[0].size = Some(40.0);
+        let ellipsis_width = content_width(&shaped(&fonts, &ellipsis));
+
+        let section_fonts = text_section_fonts(&fonts, &text, 20.0, 1.0).unwrap();
+        let result = ellipsize(
+            &mut ShapeContext::new(),
+            &section_fonts,
+            &text,
+            &clusters,
+            "…",
+            content_width(&clusters[..8]) + ellipsis_width,
+            0,
+            1.0,
+        );
+        let ellipsis = result.last().unwrap();
+        assert_eq!(ellipsis.section, 1);
+        assert_eq!(ellipsis.range, 2..2);
+        assert!(result[..result.len() - 1]
+            .iter()
+            .all(|cluster| !cluster.range.is_empty()));
+
+        let result = ellipsize(
+            &mut ShapeContext::new(),
+            &section_fonts,
+            &text,
+            &clusters,
+            "…",
+            content_width(&clusters[..5]) + 1.0,
+            0,
+            1.0,
+        );
+        assert_eq!(result.last().unwrap().section, 0);
     }
 }
@@ -0,0 +1,281 @@
+use bevy::prelude::*;
+use bevy::render::mesh::MeshVertexBufferLayoutRef;
+use bevy::render::render_resource::{
+    AsBindGroup, RenderPipelineDescriptor, ShaderRef, SpecializedMeshPipelineError,
+};
+use bevy::sprite::{Material2d, Material2dKey};
+
+use crate::fill::{specialize_text_vertex, TextFill};
+use crate::SwashImage;
+
+pub(crate) const SDF_SIZE: f32 = 64.0;
+pub(crate) const SDF_SPREAD: f32 = 12.0;
+
+pub(crate) const OUTLINED_TEXT_SDF_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(0x5d3f_2b1e_8c47_4a9b_b0e6_71f4_92ad_c815);
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct OutlinedTextSdfMaterial {
+    #[uniform(0)]
+    pub color: LinearRgba,
+    #[uniform(0)]
+    pub outline_color: LinearRgba,
+    #[uniform(0)]
+    pub outline_width: f32,
+    #[uniform(0)]
+    pub offset: f32,
+    #[uniform(0)]
+    pub softness: f32,
+    #[uniform(0)]
+    pub spread: f32,
+    #[uniform(0)]
+    pub fill_kind: u32,
+    #[uniform(0)]
+    pub fill_from: LinearRgba,
+    #[uniform(0)]
+    pub fill_to: LinearRgba,
+    #[uniform(0)]
+    pub fill_gradient: Vec4,
+    #[texture(1)]
+    #[sampler(2)]
+    pub texture: Handle<Image>,
+    #[texture(3)]
+    #[sampler(4)]
+    pub fill_texture: Option<Handle<Image>>,
+}
+
+impl OutlinedTextSdfMaterial {
+    pub(crate) fn with_fill(self, fill: &TextFill) -> Self {
+        let uniform = fill.uniform();
+
+        Self {
+            fill_kind: uniform.kind,
+            fill_from: uniform.from,
+            fill_to: uniform.to,
+            fill_gradient: uniform.gradient,
+            fill_texture: fill.texture(),
+            ..self
+        }
+    }
+}
+
+impl Material2d for OutlinedTextSdfMaterial {
+    fn vertex_shader() -> ShaderRef {
+        OUTLINED_TEXT_SDF_SHADER_HANDLE.into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        OUTLINED_TEXT_SDF_SHADER_HANDLE.into()
+    }
+
+    fn specialize(
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayoutRef,
+        _key: Material2dKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        specialize_text_vertex(descriptor, layout)
+    }
+}
+
+pub(crate) fn coverage_to_distance_field(bitmap: SwashImage) -> SwashImage {
+    map_signed_distances(bitmap, SDF_SPREAD.ceil() as usize, |distance| {
+        ((0.5 + distance / (2.0 * SDF_SPREAD)).clamp(0.0, 1.0) * 255.0).round() as u8
+    })
+}
+
+pub(crate) fn map_signed_distances(
+    mut bitmap: SwashImage,
+    spread: usize,
+    map: impl Fn(f32) -> u8,
+) -> SwashImage {
+    let glyph_width = bitmap.placement.width as usize;
+    let glyph_height = bitmap.placement.height as usize;
+
+    if glyph_width == 0 || glyph_height == 0 {
+        return bitmap;
+    }
+
+    let width = glyph_width + spread * 2;
+    let height = glyph_height + spread * 2;
+
+    // Partially covered pixels seed both transforms with their estimated
+    // distance to the edge, so anti-aliasing survives as subpixel distance.
+    let mut to_inside = vec![f32::INFINITY; width * height];
+    let mut to_outside = vec![0.0; width * height];
+    for (row, coverage) in bitmap.data.chunks_exact(glyph_width).enumerate() {
+        let start = (row + spread) * width + spread;
+        let cells = to_inside[start..start + glyph_width]
+            .iter_mut()
+            .zip(&mut to_outside[start..start + glyph_width]);
+
+        for ((to_inside, to_outside), alpha) in cells.zip(coverage) {
+            let edge = 0.5 - *alpha as f32 / 255.0;
+            (*to_inside, *to_outside) = match alpha {
+                0 => (f32::INFINITY, 0.0),
+                255 => (0.0, f32::INFINITY),
+                _ => ((edge.max(0.0)).powi(2), (edge.min(0.0)).powi(2)),
+            };
+        }
+    }
+
+    squared_distances(&mut to_inside, width, height);
+    squared_distances(&mut to_outside, width, height);
+
+    bitmap.data = to_inside
+        .iter()
+        .zip(&to_outside)
+        .map(|(to_inside, to_outside)| map(to_outside.sqrt() - to_inside.sqrt()))
+        .collect();
+
+    bitmap.placement.left -= spread as i32;
+    bitmap.placement.top += spread as i32;
+    bitmap.placement.width = width as u32;
+    bitmap.placement.height = height as u32;
+
+    bitmap
+}
+
+fn squared_distances(grid: &mut [f32], width: usize, height: usize) {
+    let length = width.max(height);
+    let mut line = vec![0.0; length];
+    let mut output = vec![0.0; length];
+    let mut vertices = vec![0; length];
+    let mut boundaries = vec![0.0; length + 1];
+
+    for x in 0..width {
+        for y in 0..height {
+            line[y] = grid[y * width + x];
+        }
+        distance_transform(
+            &line[..height],
+            &mut output[..height],
+            &mut vertices,
+            &mut boundaries,
+        );
+        for y in 0..height {
+            grid[y * width + x] = output[y];
+        }
+    }
+
+    for y in 0..height {
+        let row = &mut grid[y * width..(y + 1) * width];
+        line[..width].copy_from_slice(row);
+        distance_transform(&line[..width], row, &mut vertices, &mut boundaries);
+    }
+}
+
+fn distance_transform(
+    values: &[f32],
+    output: &mut [f32],
+    vertices: &mut [usize],
+    boundaries: &mut [f32],
+) {
+    let parabola = |q: usize| values[q] + (q * q) as f32;
+
+    let Some(first) = values.iter().position(|value| value.is_finite()) else {
+        output.fill(f32::INFINITY);
+        return;
+    };
+
+    let mut k = 0;
+    vertices[0] = first;
+    boundaries[0] = f32::NEG_INFINITY;
+    boundaries[1] = f32::INFINITY;
+
+    for (q, value) in values.iter().enumerate().skip(first + 1) {
+        if value.is_infinite() {
+            continue;
+        }
+
+        let intersection = |v: usize| (parabola(q) - parabola(v)) / (2.0 * (q - v) as f32);
+
+        let mut s = intersection(vertices[k]);
+        while s <= boundaries[k] {
+            k -= 1;
+            s = intersection(vertices[k]);
+        }
+
+        k += 1;
+        vertices[k] = q;
+        boundaries[k] = s;
+        boundaries[k + 1] = f32::INFINITY;
+    }
+
+    k = 0;
+    for (q, distance) in output.iter_mut().enumerate() {
+        while boundaries[k + 1] < q as f32 {
+            k += 1;
+        }
+        let offset = q as f32 - vertices[k] as f32;
+        *distance = offset * offset + values[vertices[k]];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 10x10 bitmap covered left of a vertical edge through column 5.
+    fn half_plane(edge_alpha: u8) -> SwashImage {
+        let mut bitmap = SwashImage::new();
+        bitmap.placement.width = 10;
+        bitmap.placement.height = 10;
+        bitmap.data = (0..100)
+            .map(|index| match index % 10 {
+                0..=4 => 255,
+                5 => edge_alpha,
+                _ => 0,
+            })
+            .collect();
+        bitmap
+    }
+
+    fn distances(bitmap: SwashImage, spread: usize) -> (usize, Vec<f32>) {
+        let width = bitmap.placement.width as usize + spread * 2;
+        let encoded = map_signed_distances(bitmap, spread, |distance| {
+            (128.0 + distance * 40.0).round() as u8
+        });
+        let decoded = encoded
+            .data
+            .iter()
+            .map(|value| (*value as f32 - 128.0) / 40.0)
+            .collect();
+        (width, decoded)
+    }
+
+    #[test]
+    fn signed_distances_are_positive_inside() {
+        let spread = 4;
+        let (width, distances) = distances(half_plane(128), spread);
+        let at = |x: usize| distances[(5 + spread) * width + x + spread];
+
+        assert_eq!(at(5), 0.0);
+        assert_eq!(at(3), 2.0);
+        assert_eq!(at(7), -2.0);
+        assert_eq!(at(0), 1.0);
+    }
+
+    #[test]
+    fn partial_coverage_moves_the_edge() {
+        let spread = 4;
+        let (width, light) = distances(half_plane(64), spread);
+        let (_, dark) = distances(half_plane(192), spread);
+        let at = |distances: &[f32], x: usize| distances[(5 + spread) * width + x + spread];
+
+        assert!(at(&light, 5) < 0.0);
+        assert!(at(&dark, 5) > 0.0);
+        assert!(at(&light, 7) < at(&dark, 7));
+        assert!(at(&light, 3) < at(&dark, 3));
+    }
+
+    #[test]
+    fn distance_field_grows_by_the_spread() {
+        let field = coverage_to_distance_field(half_plane(128));
+        let spread = SDF_SPREAD.ceil() as i32;
+
+        assert_eq!(field.placement.width, 10 + 2 * spread as u32);
+        assert_eq!(field.placement.left, -spread);
+        assert_eq!(field.placement.top, spread);
+        assert_eq!(field.data[0], 0);
+    }
+}
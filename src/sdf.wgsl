@@ -0,0 +1,36 @@
+#import bevy_swash::text::{TextFill, Vertex, VertexOutput, fill_color, text_vertex}
+
+struct OutlinedTextSdfMaterial {
+    color: vec4<f32>,
+    outline_color: vec4<f32>,
+    outline_width: f32,
+    offset: f32,
+    softness: f32,
+    spread: f32,
+    fill: TextFill,
+};
+
+@group(2) @binding(0) var<uniform> material: OutlinedTextSdfMaterial;
+@group(2) @binding(1) var sdf_texture: texture_2d<f32>;
+@group(2) @binding(2) var sdf_sampler: sampler;
+@group(2) @binding(3) var fill_texture: texture_2d<f32>;
+@group(2) @binding(4) var fill_sampler: sampler;
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    return text_vertex(vertex);
+}
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    let distance = (textureSample(sdf_texture, sdf_sampler, mesh.uv).a - 0.5) * 2.0 * material.spread - material.offset;
+    let smoothing = max(fwidth(distance) * 0.5 + material.softness, 0.0001);
+
+    let fill = smoothstep(-smoothing, smoothing, distance);
+    let coverage = smoothstep(-smoothing, smoothing, distance + material.outline_width);
+    let color = mix(material.outline_color, material.color, fill)
+        * fill_color(material.fill, mesh.fill_uv, fill_texture, fill_sampler)
+        * mesh.color;
+
+    return vec4<f32>(color.rgb, color.a * coverage);
+}
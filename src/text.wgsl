@@ -0,0 +1,63 @@
+#define_import_path bevy_swash::text
+
+#import bevy_sprite::mesh2d_functions::{get_world_from_local, mesh2d_position_local_to_clip}
+
+struct TextFill {
+    kind: u32,
+    start: vec4<f32>,
+    end: vec4<f32>,
+    gradient: vec4<f32>,
+};
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) fill_uv: vec2<f32>,
+    @location(3) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) fill_uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+};
+
+fn text_vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    let world_from_local = get_world_from_local(vertex.instance_index);
+    out.position = mesh2d_position_local_to_clip(world_from_local, vec4<f32>(vertex.position, 1.0));
+    out.uv = vertex.uv;
+    out.fill_uv = vertex.fill_uv;
+    out.color = vertex.color;
+    return out;
+}
+
+fn fill_color(
+    fill: TextFill,
+    uv: vec2<f32>,
+    fill_texture: texture_2d<f32>,
+    fill_sampler: sampler,
+) -> vec4<f32> {
+    let texel = textureSample(fill_texture, fill_sampler, uv);
+
+    switch fill.kind {
+        case 1u: {
+            let direction = fill.gradient.xy;
+            let extent = max(abs(direction.x) + abs(direction.y), 0.0001);
+            let t = dot(uv - vec2<f32>(0.5), direction) / extent + 0.5;
+            return mix(fill.start, fill.end, clamp(t, 0.0, 1.0));
+        }
+        case 2u: {
+            let t = length(uv - fill.gradient.xy) / max(fill.gradient.z, 0.0001);
+            return mix(fill.start, fill.end, clamp(t, 0.0, 1.0));
+        }
+        case 3u: {
+            return texel * fill.start;
+        }
+        default: {
+            return fill.start;
+        }
+    }
+}